@@ -0,0 +1,66 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! The registry of applets. Each applet describes its own command line and how to run it, so
+//! adding one is a matter of implementing `Applet` and listing it in `APPLETS`.
+
+use crate::ExitCode;
+use clap::{App, ArgMatches};
+
+pub trait Applet: Sync {
+    /// The name the applet is run by, either as `busycrate <name>` or through a link with that
+    /// name
+    fn name(&self) -> &'static str;
+
+    /// Other names the applet can be run by
+    fn aliases(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    /// The clap definition of the applet's command line, named after the applet
+    fn command(&self) -> App<'static, 'static>;
+
+    /// Runs the applet with the arguments matched by `command`
+    fn run(&self, matches: &ArgMatches) -> ExitCode;
+}
+
+/// Every applet compiled into busycrate, in the order they're listed by `--list`. Each one is
+/// behind a cargo feature of the same name.
+pub const APPLETS: &[&dyn Applet] = &[
+    #[cfg(feature = "ls")]
+    &crate::ls::Ls,
+    #[cfg(feature = "mkdir")]
+    &crate::mkdir::Mkdir,
+    #[cfg(feature = "rmdir")]
+    &crate::rmdir::Rmdir,
+    #[cfg(feature = "touch")]
+    &crate::touch::Touch,
+];
+
+/// Looks up an applet by its name or one of its aliases
+pub fn find(name: &str) -> Option<&'static dyn Applet> {
+    APPLETS
+        .iter()
+        .copied()
+        .find(|applet| applet.name() == name || applet.aliases().contains(&name))
+}
+
+#[cfg(any(feature = "ls", feature = "mkdir", feature = "rmdir", feature = "touch"))]
+pub fn map_os_args_to_path_vec<'a>(os_args: clap::OsValues<'a>) -> Vec<&'a std::path::Path> {
+    os_args.map(|os_str| os_str.as_ref()).collect()
+}
+
+/// Finds which of several mutually exclusive flags came last on the command line, returning the
+/// value associated with it
+#[cfg(feature = "ls")]
+pub fn last_present<T: Copy>(matches: &clap::ArgMatches, options: &[(&str, T)]) -> Option<T> {
+    options
+        .iter()
+        .filter_map(|&(name, value)| {
+            let last_index = matches.indices_of(name).and_then(|mut i| i.next_back());
+            last_index.map(|i| (i, value))
+        })
+        .max_by_key(|&(i, _)| i)
+        .map(|(_, value)| value)
+}
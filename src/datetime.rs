@@ -0,0 +1,650 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! Conversion of timestamps to and from the local time zone, rendering them with `strftime`-style
+//! format strings, and parsing the human readable dates given on the command line. This is done by
+//! hand rather than through libc's `strftime` so that nanoseconds (`%N`) can be supported, and so
+//! that the output doesn't depend on the locale.
+
+use nix::sys::time::TimeSpec;
+use std::convert::TryFrom;
+use std::ffi::CStr;
+use std::fmt::Write;
+
+const DAYS: [&str; 7] = ["Sunday", "Monday", "Tuesday", "Wednesday", "Thursday", "Friday", "Saturday"];
+const MONTHS: [&str; 12] = [
+    "January", "February", "March", "April", "May", "June", "July", "August", "September",
+    "October", "November", "December",
+];
+
+extern "C" {
+    // not exposed by the libc crate
+    fn tzset();
+}
+
+/// Reads the time zone from $TZ. This needs to be done before calling `local_time`, since
+/// localtime_r isn't required to do it.
+pub fn init_timezone() {
+    unsafe { tzset() };
+}
+
+/// Breaks seconds since the epoch down into the local time zone
+pub fn local_time(secs: i64) -> libc::tm {
+    let t = secs as libc::time_t;
+    // localtime_r fully initializes the struct, so zeroing it is just to have something valid to
+    // hand over
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    unsafe { libc::localtime_r(&t, &mut tm) };
+    tm
+}
+
+/// Breaks seconds since the epoch down into UTC
+fn utc_time(secs: i64) -> libc::tm {
+    let t = secs as libc::time_t;
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    unsafe { libc::gmtime_r(&t, &mut tm) };
+    tm
+}
+
+/// Converts a broken down time back to seconds since the epoch. Out of range fields are
+/// normalized, so e.g. a `tm_mday` of 0 is the last day of the previous month. `offset` is the
+/// time zone the time is in as seconds east of UTC, or `None` for the local time zone.
+fn epoch_seconds(mut tm: libc::tm, offset: Option<i64>) -> i64 {
+    match offset {
+        Some(offset) => unsafe { libc::timegm(&mut tm) as i64 - offset },
+        None => {
+            // let mktime work out whether daylight saving time applies
+            tm.tm_isdst = -1;
+            unsafe { libc::mktime(&mut tm) as i64 }
+        }
+    }
+}
+
+fn timespec(secs: i64, nsec: i64) -> TimeSpec {
+    libc::timespec {
+        tv_sec: secs as libc::time_t,
+        tv_nsec: nsec as libc::c_long,
+    }
+    .into()
+}
+
+/// Renders a time according to a `strftime` format string. Along with the usual conversions, `%N`
+/// gives the nanoseconds, and `%3N` and the like give the first few digits of them.
+pub fn format(fmt: &str, tm: &libc::tm, nsec: i64) -> String {
+    let mut out = String::with_capacity(fmt.len() * 2);
+    let mut chars = fmt.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            out.push(c);
+            continue;
+        }
+
+        // only %N takes a width
+        let mut width = None;
+        while let Some(digit) = chars.peek().and_then(|c| c.to_digit(10)) {
+            width = Some(width.unwrap_or(0) * 10 + digit as usize);
+            chars.next();
+        }
+
+        let conversion = match chars.next() {
+            Some(conversion) => conversion,
+            None => {
+                // a trailing '%' is just a '%'
+                out.push('%');
+                break;
+            }
+        };
+        // writing to a String can't fail
+        let _ = format_conversion(&mut out, conversion, width, tm, nsec);
+    }
+
+    out
+}
+
+fn format_conversion(
+    out: &mut String,
+    conversion: char,
+    width: Option<usize>,
+    tm: &libc::tm,
+    nsec: i64,
+) -> std::fmt::Result {
+    let year = tm.tm_year as i64 + 1900;
+    let day = DAYS[tm.tm_wday as usize % 7];
+    let month = MONTHS[tm.tm_mon as usize % 12];
+    let hour12 = match tm.tm_hour % 12 {
+        0 => 12,
+        h => h,
+    };
+
+    match conversion {
+        'a' => out.push_str(&day[..3]),
+        'A' => out.push_str(day),
+        'b' | 'h' => out.push_str(&month[..3]),
+        'B' => out.push_str(month),
+        'c' => return write!(out, "{}", format("%a %b %e %H:%M:%S %Y", tm, nsec)),
+        'C' => write!(out, "{:02}", year / 100)?,
+        'd' => write!(out, "{:02}", tm.tm_mday)?,
+        'D' => return write!(out, "{}", format("%m/%d/%y", tm, nsec)),
+        'e' => write!(out, "{:>2}", tm.tm_mday)?,
+        'F' => return write!(out, "{}", format("%Y-%m-%d", tm, nsec)),
+        'H' => write!(out, "{:02}", tm.tm_hour)?,
+        'I' => write!(out, "{:02}", hour12)?,
+        'j' => write!(out, "{:03}", tm.tm_yday + 1)?,
+        'k' => write!(out, "{:>2}", tm.tm_hour)?,
+        'l' => write!(out, "{:>2}", hour12)?,
+        'm' => write!(out, "{:02}", tm.tm_mon + 1)?,
+        'M' => write!(out, "{:02}", tm.tm_min)?,
+        'n' => out.push('\n'),
+        'N' => {
+            let digits = format!("{:09}", nsec);
+            let width = width.unwrap_or(9).clamp(1, 9);
+            out.push_str(&digits[..width]);
+        }
+        'p' => out.push_str(if tm.tm_hour < 12 { "AM" } else { "PM" }),
+        'P' => out.push_str(if tm.tm_hour < 12 { "am" } else { "pm" }),
+        'r' => return write!(out, "{}", format("%I:%M:%S %p", tm, nsec)),
+        'R' => return write!(out, "{}", format("%H:%M", tm, nsec)),
+        's' => {
+            // mktime normalizes its argument, so hand it a copy
+            let mut copy = *tm;
+            write!(out, "{}", unsafe { libc::mktime(&mut copy) })?
+        }
+        'S' => write!(out, "{:02}", tm.tm_sec)?,
+        't' => out.push('\t'),
+        'T' => return write!(out, "{}", format("%H:%M:%S", tm, nsec)),
+        'u' => write!(out, "{}", if tm.tm_wday == 0 { 7 } else { tm.tm_wday })?,
+        'w' => write!(out, "{}", tm.tm_wday)?,
+        'y' => write!(out, "{:02}", year % 100)?,
+        'Y' => write!(out, "{}", year)?,
+        'z' => {
+            let offset = tm.tm_gmtoff;
+            let sign = if offset < 0 { '-' } else { '+' };
+            let minutes = offset.abs() / 60;
+            write!(out, "{}{:02}{:02}", sign, minutes / 60, minutes % 60)?
+        }
+        'Z' => {
+            if !tm.tm_zone.is_null() {
+                let zone = unsafe { CStr::from_ptr(tm.tm_zone) };
+                out.push_str(&zone.to_string_lossy());
+            }
+        }
+        '%' => out.push('%'),
+        // unknown conversions are left as they were written
+        other => {
+            out.push('%');
+            out.push(other);
+        }
+    }
+    Ok(())
+}
+
+/// Parses a human readable date like the ones GNU's `touch -d` accepts: `@1700000000.5`,
+/// `2024-01-01 12:00:00.5 +0200`, `yesterday`, `-2 hours`, `3 days ago`, and combinations of
+/// those. Relative items and times without a date are taken from `base`. Returns `None` if the
+/// date can't be understood.
+pub fn parse_date(spec: &str, base: TimeSpec) -> Option<TimeSpec> {
+    let spec = spec.trim();
+    if let Some(epoch) = spec.strip_prefix('@') {
+        return parse_epoch(epoch);
+    }
+
+    let mut items = DateItems::default();
+    let mut parser = DateParser { rest: spec };
+    while parser.skip_whitespace() {
+        parser.item(&mut items)?;
+    }
+    items.resolve(base)
+}
+
+/// Parses a POSIX `touch -t` timestamp, `[[CC]YY]MMDDhhmm[.SS]`, in the local time zone. Two digit
+/// years from 69 to 99 are in the 1900s and the rest are in the 2000s, and a missing year is the
+/// one `now` is in. Returns `None` if the timestamp is malformed or isn't a real date.
+pub fn parse_posix_stamp(spec: &str, now: TimeSpec) -> Option<TimeSpec> {
+    let (stamp, seconds) = match spec.find('.') {
+        Some(i) => (&spec[..i], Some(&spec[i + 1..])),
+        None => (spec, None),
+    };
+    let all_digits = |s: &str| !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit());
+    if !all_digits(stamp) || !seconds.map(|s| s.len() == 2 && all_digits(s)).unwrap_or(true) {
+        return None;
+    }
+
+    // every field is two digits, so parsing can't fail from here on
+    let field = |s: &str| s.parse::<i32>().unwrap_or(0);
+    let (year, rest) = match stamp.len() {
+        8 => (local_time(now.tv_sec()).tm_year + 1900, stamp),
+        10 => match field(&stamp[..2]) {
+            year @ 69..=99 => (1900 + year, &stamp[2..]),
+            year => (2000 + year, &stamp[2..]),
+        },
+        12 => (field(&stamp[..4]), &stamp[4..]),
+        _ => return None,
+    };
+    let month = field(&rest[..2]);
+    let day = field(&rest[2..4]);
+    let hour = field(&rest[4..6]);
+    let minute = field(&rest[6..8]);
+    let second = seconds.map(field).unwrap_or(0);
+
+    // 60 seconds allows for leap seconds
+    let valid_day = (1..=12).contains(&month) && day >= 1 && day <= days_in_month(year, month);
+    if !valid_day || hour > 23 || minute > 59 || second > 60 {
+        return None;
+    }
+
+    let mut tm = local_time(now.tv_sec());
+    tm.tm_year = year - 1900;
+    tm.tm_mon = month - 1;
+    tm.tm_mday = day;
+    tm.tm_hour = hour;
+    tm.tm_min = minute;
+    tm.tm_sec = second;
+    Some(timespec(epoch_seconds(tm, None), 0))
+}
+
+/// Seconds since the epoch, with an optional fraction
+fn parse_epoch(spec: &str) -> Option<TimeSpec> {
+    let (negative, spec) = match spec.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, spec.strip_prefix('+').unwrap_or(spec)),
+    };
+    let mut parser = DateParser { rest: spec };
+    let secs: i64 = parser.digits()?.parse().ok()?;
+    let nsec = if parser.eat('.') || parser.eat(',') { parser.fraction()? } else { 0 };
+    if !parser.rest.is_empty() {
+        return None;
+    }
+
+    match (negative, nsec) {
+        (false, _) => Some(timespec(secs, nsec)),
+        (true, 0) => Some(timespec(-secs, 0)),
+        // nanoseconds always count forwards, so -1.25 is 2 seconds before the epoch plus 0.75
+        (true, _) => Some(timespec(-secs - 1, 1_000_000_000 - nsec)),
+    }
+}
+
+/// Everything that was found in a date string, before it's applied to the base time
+#[derive(Default)]
+struct DateItems {
+    /// Year, month and day
+    date: Option<(i32, i32, i32)>,
+    /// Hour, minute, second and nanoseconds
+    time: Option<(i32, i32, i32, i64)>,
+    /// Seconds east of UTC
+    zone: Option<i64>,
+    /// Amounts to move the time by, in the order they were given
+    relative: Vec<(RelativeUnit, i64)>,
+}
+
+#[derive(Clone, Copy)]
+enum RelativeUnit {
+    Years,
+    Months,
+    Days,
+    Seconds,
+}
+
+impl RelativeUnit {
+    /// The unit named by a word like "hours" or "min", and how many of it the word stands for
+    fn from_word(word: &str) -> Option<(RelativeUnit, i64)> {
+        let singular = word.strip_suffix('s').unwrap_or(word);
+        match singular {
+            "year" => Some((RelativeUnit::Years, 1)),
+            "month" => Some((RelativeUnit::Months, 1)),
+            "fortnight" => Some((RelativeUnit::Days, 14)),
+            "week" => Some((RelativeUnit::Days, 7)),
+            "day" => Some((RelativeUnit::Days, 1)),
+            "hour" => Some((RelativeUnit::Seconds, 60 * 60)),
+            "minute" | "min" => Some((RelativeUnit::Seconds, 60)),
+            "second" | "sec" => Some((RelativeUnit::Seconds, 1)),
+            _ => None,
+        }
+    }
+}
+
+impl DateItems {
+    /// Applies the items to `base`. Returns `None` if a relative item moves the time further than
+    /// can be represented.
+    fn resolve(&self, base: TimeSpec) -> Option<TimeSpec> {
+        let mut tm = match self.zone {
+            Some(offset) => utc_time(base.tv_sec() + offset),
+            None => local_time(base.tv_sec()),
+        };
+        let mut nsec = base.tv_nsec();
+
+        if let Some((year, month, day)) = self.date {
+            tm.tm_year = year - 1900;
+            tm.tm_mon = month - 1;
+            tm.tm_mday = day;
+            // a date on its own means the start of that day
+            tm.tm_hour = 0;
+            tm.tm_min = 0;
+            tm.tm_sec = 0;
+            nsec = 0;
+        }
+        if let Some((hour, minute, second, time_nsec)) = self.time {
+            tm.tm_hour = hour;
+            tm.tm_min = minute;
+            tm.tm_sec = second;
+            nsec = time_nsec;
+        }
+
+        // days, months and years follow the calendar, so "1 day ago" is the same time of day
+        // even across a daylight saving change. Seconds are exact.
+        let mut seconds: i64 = 0;
+        for &(unit, amount) in self.relative.iter() {
+            let field = match unit {
+                RelativeUnit::Years => &mut tm.tm_year,
+                RelativeUnit::Months => &mut tm.tm_mon,
+                RelativeUnit::Days => &mut tm.tm_mday,
+                RelativeUnit::Seconds => {
+                    seconds = seconds.checked_add(amount)?;
+                    continue;
+                }
+            };
+            *field = field.checked_add(i32::try_from(amount).ok()?)?;
+        }
+
+        let secs = epoch_seconds(tm, self.zone).checked_add(seconds)?;
+        Some(timespec(secs, nsec))
+    }
+}
+
+struct DateParser<'a> {
+    rest: &'a str,
+}
+
+impl<'a> DateParser<'a> {
+    /// Returns whether there's anything left to parse
+    fn skip_whitespace(&mut self) -> bool {
+        self.rest = self.rest.trim_start();
+        !self.rest.is_empty()
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        match self.rest.strip_prefix(c) {
+            Some(rest) => {
+                self.rest = rest;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn take_while(&mut self, f: impl Fn(char) -> bool) -> &'a str {
+        let end = self.rest.find(|c| !f(c)).unwrap_or(self.rest.len());
+        let (taken, rest) = self.rest.split_at(end);
+        self.rest = rest;
+        taken
+    }
+
+    fn digits(&mut self) -> Option<&'a str> {
+        Some(self.take_while(|c| c.is_ascii_digit())).filter(|d| !d.is_empty())
+    }
+
+    fn number(&mut self) -> Option<i32> {
+        self.digits()?.parse().ok()
+    }
+
+    /// The digits after a decimal point, as nanoseconds. Anything past nanoseconds is dropped.
+    fn fraction(&mut self) -> Option<i64> {
+        let digits = self.digits()?;
+        let digits = &digits[..digits.len().min(9)];
+        let nsec: i64 = digits.parse().ok()?;
+        Some(nsec * 10_i64.pow(9 - digits.len() as u32))
+    }
+
+    fn item(&mut self, items: &mut DateItems) -> Option<()> {
+        let first = self.rest.chars().next()?;
+
+        if first.is_ascii_digit() {
+            // look past the number to find out what kind of item it starts
+            let start = self.rest;
+            let number = self.digits()?;
+            let next = self.rest.chars().next();
+            self.rest = start;
+            return match next {
+                Some('-') => self.date(items),
+                Some(':') => self.time(items),
+                _ => {
+                    self.digits();
+                    self.relative(items, number.parse().ok()?)
+                }
+            };
+        }
+
+        if first == '+' || first == '-' {
+            self.rest = &self.rest[1..];
+            let sign = if first == '-' { -1 } else { 1 };
+            self.skip_whitespace();
+            let start = self.rest;
+            let number: i64 = self.digits()?.parse().ok()?;
+
+            // a signed number is a relative item if a unit follows it, and is otherwise the time
+            // zone of the time before it
+            self.skip_whitespace();
+            if self.rest.starts_with(|c: char| c.is_ascii_alphabetic()) {
+                return self.relative(items, sign * number);
+            }
+            self.rest = start;
+            if items.time.is_none() || items.zone.is_some() {
+                return None;
+            }
+            items.zone = Some(sign * self.zone_offset()?);
+            return Some(());
+        }
+
+        let word = self.take_while(|c| c.is_ascii_alphabetic()).to_ascii_lowercase();
+        match word.as_str() {
+            "now" | "today" => {}
+            "yesterday" => items.relative.push((RelativeUnit::Days, -1)),
+            "tomorrow" => items.relative.push((RelativeUnit::Days, 1)),
+            "ago" => {
+                let last = items.relative.last_mut()?;
+                last.1 = -last.1;
+            }
+            "utc" | "gmt" | "z" if items.zone.is_none() => items.zone = Some(0),
+            "am" | "pm" => {
+                let time = items.time.as_mut()?;
+                if !(1..=12).contains(&time.0) {
+                    return None;
+                }
+                time.0 = time.0 % 12 + if word == "pm" { 12 } else { 0 };
+            }
+            _ => {
+                // a unit on its own, like "hour ago", means one of it
+                let (unit, scale) = RelativeUnit::from_word(&word)?;
+                items.relative.push((unit, scale));
+            }
+        }
+        Some(())
+    }
+
+    /// `YYYY-MM-DD`, optionally followed by `T` and a time
+    fn date(&mut self, items: &mut DateItems) -> Option<()> {
+        let year = self.number()?;
+        if !self.eat('-') {
+            return None;
+        }
+        let month = self.number()?;
+        if !self.eat('-') {
+            return None;
+        }
+        let day = self.number()?;
+
+        if !(1..=12).contains(&month) || day < 1 || day > days_in_month(year, month) {
+            return None;
+        }
+        if items.date.replace((year, month, day)).is_some() {
+            return None;
+        }
+
+        if self.eat('T') || self.eat('t') {
+            self.time(items)?;
+        }
+        Some(())
+    }
+
+    /// `hh:mm[:ss[.fraction]]`
+    fn time(&mut self, items: &mut DateItems) -> Option<()> {
+        let hour = self.number()?;
+        if !self.eat(':') {
+            return None;
+        }
+        let minute = self.number()?;
+        let second = if self.eat(':') { self.number()? } else { 0 };
+        let nsec = if self.eat('.') || self.eat(',') { self.fraction()? } else { 0 };
+
+        // 60 seconds allows for leap seconds
+        if hour > 23 || minute > 59 || second > 60 {
+            return None;
+        }
+        if items.time.replace((hour, minute, second, nsec)).is_some() {
+            return None;
+        }
+        Some(())
+    }
+
+    /// A number followed by a unit, and possibly "ago"
+    fn relative(&mut self, items: &mut DateItems, amount: i64) -> Option<()> {
+        self.skip_whitespace();
+        let word = self.take_while(|c| c.is_ascii_alphabetic()).to_ascii_lowercase();
+        let (unit, scale) = RelativeUnit::from_word(&word)?;
+        items.relative.push((unit, amount.checked_mul(scale)?));
+        Some(())
+    }
+
+    /// The part of a time zone after the sign: `hh`, `hhmm` or `hh:mm`, returned in seconds
+    fn zone_offset(&mut self) -> Option<i64> {
+        let digits = self.digits()?;
+        let (hours, minutes): (i64, i64) = match digits.len() {
+            1 | 2 if self.eat(':') => (digits.parse().ok()?, self.digits()?.parse().ok()?),
+            1 | 2 => (digits.parse().ok()?, 0),
+            4 => (digits[..2].parse().ok()?, digits[2..].parse().ok()?),
+            _ => return None,
+        };
+        if hours > 24 || minutes > 59 {
+            return None;
+        }
+        Some(hours * 60 * 60 + minutes * 60)
+    }
+}
+
+fn days_in_month(year: i32, month: i32) -> i32 {
+    let leap = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
+    match month {
+        2 if leap => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Dates without a zone are in local time, so pin that down to UTC
+    fn in_utc() {
+        std::env::set_var("TZ", "UTC0");
+        init_timezone();
+    }
+
+    fn ts(secs: i64, nsec: i64) -> Option<TimeSpec> {
+        Some(timespec(secs, nsec))
+    }
+
+    /// 2024-06-15 00:00:00 UTC
+    const BASE: i64 = 1_718_409_600;
+
+    fn date(spec: &str) -> Option<TimeSpec> {
+        parse_date(spec, timespec(BASE, 0))
+    }
+
+    #[test]
+    fn absolute_dates() {
+        in_utc();
+        assert_eq!(date("2024-01-01 12:00:00.5 +0200"), ts(1_704_103_200, 500_000_000));
+        assert_eq!(date("2024-01-01"), ts(1_704_067_200, 0));
+        assert_eq!(date("2024-01-01T12:00:30"), ts(1_704_110_430, 0));
+        // a time on its own is on the base's day
+        assert_eq!(date("12:00"), ts(BASE + 12 * 60 * 60, 0));
+        assert_eq!(date("1:30pm"), ts(BASE + 13 * 60 * 60 + 30 * 60, 0));
+    }
+
+    #[test]
+    fn zone_offsets() {
+        in_utc();
+        let midnight = 1_704_067_200;
+        assert_eq!(date("2024-01-01T00:00Z"), ts(midnight, 0));
+        assert_eq!(date("2024-01-01 00:00 +05"), ts(midnight - 5 * 60 * 60, 0));
+        assert_eq!(date("2024-01-01 00:00 -05:30"), ts(midnight + 5 * 60 * 60 + 30 * 60, 0));
+        assert_eq!(date("2024-01-01 00:00 +0130"), ts(midnight - 90 * 60, 0));
+        assert_eq!(date("2024-01-01 00:00 +25"), None);
+    }
+
+    #[test]
+    fn epochs() {
+        assert_eq!(date("@1700000000"), ts(1_700_000_000, 0));
+        assert_eq!(date("@1700000000.5"), ts(1_700_000_000, 500_000_000));
+        assert_eq!(date("@-1"), ts(-1, 0));
+        assert_eq!(date("@-1.25"), ts(-2, 750_000_000));
+        assert_eq!(date("@1.5x"), None);
+    }
+
+    #[test]
+    fn relative_dates() {
+        in_utc();
+        let day = 24 * 60 * 60;
+        assert_eq!(date("now"), ts(BASE, 0));
+        assert_eq!(date("yesterday"), ts(BASE - day, 0));
+        assert_eq!(date("tomorrow"), ts(BASE + day, 0));
+        assert_eq!(date("-2 hours"), ts(BASE - 2 * 60 * 60, 0));
+        assert_eq!(date("3 days ago"), ts(BASE - 3 * day, 0));
+        assert_eq!(date("1 week"), ts(BASE + 7 * day, 0));
+        assert_eq!(date("2024-01-01 +1 month"), ts(1_704_067_200 + 31 * day, 0));
+    }
+
+    #[test]
+    fn invalid_dates() {
+        in_utc();
+        assert_eq!(date(""), ts(BASE, 0));
+        assert_eq!(date("2024-02-30"), None);
+        assert_eq!(date("2023-02-29"), None);
+        assert_eq!(date("2024-13-01"), None);
+        assert_eq!(date("24:00"), None);
+        assert_eq!(date("2 fortnights and a bit"), None);
+        assert_eq!(date("2024-01-01 2024-01-02"), None);
+    }
+
+    #[test]
+    fn out_of_range_relative_dates() {
+        in_utc();
+        assert_eq!(date("2147483647 years"), None);
+        assert_eq!(date("99999999999 days"), None);
+        assert_eq!(date("9223372036854775807 hours"), None);
+        assert_eq!(date("9223372036854775807 seconds 1 second"), None);
+    }
+
+    #[test]
+    fn posix_stamps() {
+        in_utc();
+        let now = timespec(BASE, 0);
+        assert_eq!(parse_posix_stamp("202401011200.30", now), ts(1_704_110_430, 0));
+        assert_eq!(parse_posix_stamp("2401011200.30", now), ts(1_704_110_430, 0));
+        // without a year, it's the current one
+        assert_eq!(parse_posix_stamp("01011200", now), ts(1_704_110_400, 0));
+
+        // two digit years from 69 on are in the 1900s
+        assert_eq!(parse_posix_stamp("6901010000", now), ts(-31_536_000, 0));
+        assert_eq!(parse_posix_stamp("6801010000", now), ts(3_092_601_600, 0));
+
+        assert_eq!(parse_posix_stamp("202402300000", now), None);
+        assert_eq!(parse_posix_stamp("2401011200.5", now), None);
+        assert_eq!(parse_posix_stamp("2401012400", now), None);
+        assert_eq!(parse_posix_stamp("123", now), None);
+        assert_eq!(parse_posix_stamp("2024-01-01", now), None);
+    }
+}
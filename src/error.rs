@@ -0,0 +1,136 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! The errors applets run into while working on files. Every applet reports these the same way,
+//! as `applet: 'path': cannot <operation>: <reason>`, and turns them into an exit code with the
+//! same policy, so that e.g. a missing file means the same exit code no matter which applet
+//! noticed.
+
+use crate::ExitCode;
+use nix::errno::Errno;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// What an applet was trying to do when it failed
+#[cfg_attr(not(all(feature = "ls", feature = "mkdir", feature = "rmdir", feature = "touch")), allow(dead_code))]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Operation {
+    GetTime,
+    Stat,
+    ReadDir,
+    ReadLink,
+    CreateFile,
+    CreateDir,
+    RemoveDir,
+    SetTimes,
+    SetPermissions,
+    SetContext,
+    Close,
+    FindExe,
+    Link,
+    Remove,
+}
+
+impl Operation {
+    fn description(self) -> &'static str {
+        match self {
+            Operation::GetTime => "get system time",
+            Operation::Stat => "stat",
+            Operation::ReadDir => "read directory",
+            Operation::ReadLink => "read link",
+            Operation::CreateFile => "create file",
+            Operation::CreateDir => "create directory",
+            Operation::RemoveDir => "remove directory",
+            Operation::SetTimes => "modify times",
+            Operation::SetPermissions => "set permissions",
+            Operation::SetContext => "set security context",
+            Operation::Close => "close",
+            Operation::FindExe => "find the busycrate binary",
+            Operation::Link => "link",
+            Operation::Remove => "remove",
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Error {
+    pub op: Operation,
+    /// The file being worked on, if there was one
+    pub path: Option<PathBuf>,
+    pub errno: Errno,
+}
+
+impl Error {
+    // unused when busycrate is built without any applets
+    #[allow(dead_code)]
+    pub fn new(op: Operation, path: &Path, e: nix::Error) -> Self {
+        Self { op, path: Some(path.to_path_buf()), errno: errno_of(e) }
+    }
+
+    /// An error that isn't about any particular file
+    #[cfg_attr(not(feature = "touch"), allow(dead_code))]
+    pub fn without_path(op: Operation, e: nix::Error) -> Self {
+        Self { op, path: None, errno: errno_of(e) }
+    }
+
+    pub fn from_io(op: Operation, path: Option<&Path>, e: std::io::Error) -> Self {
+        // std only makes errors without an OS code for things it checks itself, which are all
+        // some kind of invalid input
+        let errno = e.raw_os_error().map(Errno::from_i32).unwrap_or(Errno::EINVAL);
+        Self { op, path: path.map(Path::to_path_buf), errno }
+    }
+
+    /// The exit code an applet should use for this error. The reason takes priority since it
+    /// means the same thing everywhere, and the operation decides the rest.
+    pub fn exit_code(&self) -> ExitCode {
+        match (self.errno, self.op) {
+            (Errno::ENOENT, _) => ExitCode::NotFound,
+            (Errno::ENOTDIR, _) => ExitCode::NotDir,
+            (Errno::ENOTEMPTY, _) => ExitCode::NotEmpty,
+            // some systems report non-empty directories this way
+            (Errno::EEXIST, Operation::RemoveDir) => ExitCode::NotEmpty,
+            (Errno::EEXIST, _) => ExitCode::Exists,
+            (Errno::EACCES, _) | (Errno::EPERM, _) => ExitCode::Permission,
+            (_, Operation::GetTime) => ExitCode::Time,
+            (_, Operation::ReadDir) => ExitCode::ReadDir,
+            (_, Operation::Stat) | (_, Operation::ReadLink) | (_, Operation::SetTimes) => {
+                ExitCode::Stat
+            }
+            _ => ExitCode::UnknownErr,
+        }
+    }
+
+    /// Prints the error to stderr on behalf of `applet`, returning the exit code it calls for
+    pub fn report(&self, applet: &str) -> ExitCode {
+        eprintln!("{}: {}", applet, self);
+        self.exit_code()
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(path) = &self.path {
+            write!(f, "'{}': ", path.display())?;
+        }
+        write!(f, "cannot {}: {}", self.op.description(), self.errno.desc())
+    }
+}
+
+/// Prints a problem that doesn't come from the OS, like an invalid argument, in the same format as
+/// `Error`. `path` is the file it's about, if there is one.
+pub fn report(applet: &str, path: Option<&Path>, message: impl fmt::Display) {
+    match path {
+        Some(path) => eprintln!("{}: '{}': {}", applet, path.display(), message),
+        None => eprintln!("{}: {}", applet, message),
+    }
+}
+
+fn errno_of(e: nix::Error) -> Errno {
+    match e {
+        nix::Error::Sys(errno) => errno,
+        // paths with a NUL in them or that are too long to copy onto the stack
+        nix::Error::InvalidPath | nix::Error::InvalidUtf8 => Errno::EINVAL,
+        nix::Error::UnsupportedOperation => Errno::EOPNOTSUPP,
+    }
+}
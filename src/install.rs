@@ -0,0 +1,103 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! Installation of links named after each applet, so that e.g. running `ls` runs busycrate's ls.
+//! Like BusyBox, hardlinks are used by default since they keep working if the binary is moved
+//! around, but symlinks work across filesystems.
+
+use crate::applet;
+use crate::error::{self, Error, Operation};
+use crate::ExitCode;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+
+/// What errors are reported as coming from
+const NAME: &str = "busycrate";
+
+pub struct Args<'a> {
+    /// Directory to put the links in
+    pub dir: &'a Path,
+    /// Make symlinks instead of hardlinks
+    pub symbolic: bool,
+    /// Replace files that are in the way, even if they aren't links to busycrate
+    pub force: bool,
+}
+
+pub fn install(args: Args) -> ExitCode {
+    let exe = match current_exe() {
+        Ok(exe) => exe,
+        Err(code) => return code,
+    };
+
+    let mut status = ExitCode::Success;
+    for name in link_names() {
+        let link = args.dir.join(name);
+
+        if link.symlink_metadata().is_ok() {
+            // links from an earlier install get replaced, in case it was done with or without -s
+            if !args.force && !is_link_to(&link, &exe) {
+                let message = "skipped since it already exists, use --force to replace it";
+                error::report(NAME, Some(&link), message);
+                continue;
+            }
+            if let Err(e) = std::fs::remove_file(&link) {
+                status = status.max(Error::from_io(Operation::Remove, Some(&link), e).report(NAME));
+                continue;
+            }
+        }
+
+        let result = if args.symbolic {
+            std::os::unix::fs::symlink(&exe, &link)
+        } else {
+            std::fs::hard_link(&exe, &link)
+        };
+        if let Err(e) = result {
+            status = status.max(Error::from_io(Operation::Link, Some(&link), e).report(NAME));
+        }
+    }
+
+    status
+}
+
+/// Removes the links that `install` would make, leaving alone anything with the same name that
+/// isn't a link to this binary
+pub fn uninstall(dir: &Path) -> ExitCode {
+    let exe = match current_exe() {
+        Ok(exe) => exe,
+        Err(code) => return code,
+    };
+
+    let mut status = ExitCode::Success;
+    for name in link_names() {
+        let link = dir.join(name);
+        if !is_link_to(&link, &exe) {
+            continue;
+        }
+        if let Err(e) = std::fs::remove_file(&link) {
+            status = status.max(Error::from_io(Operation::Remove, Some(&link), e).report(NAME));
+        }
+    }
+
+    status
+}
+
+/// The name of every compiled in applet, along with their aliases
+fn link_names() -> impl Iterator<Item = &'static str> {
+    applet::APPLETS
+        .iter()
+        .flat_map(|applet| std::iter::once(applet.name()).chain(applet.aliases().iter().copied()))
+}
+
+fn current_exe() -> Result<PathBuf, ExitCode> {
+    std::env::current_exe().map_err(|e| Error::from_io(Operation::FindExe, None, e).report(NAME))
+}
+
+/// Whether `link` is either a hardlink or a symlink to `exe`. Both resolve to the same inode, so
+/// that's what gets compared.
+fn is_link_to(link: &Path, exe: &Path) -> bool {
+    match (link.metadata(), exe.metadata()) {
+        (Ok(link), Ok(exe)) => link.dev() == exe.dev() && link.ino() == exe.ino(),
+        _ => false,
+    }
+}
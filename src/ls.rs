@@ -2,114 +2,845 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
 
-use nix::fcntl::OFlag;
-use nix::sys::stat::Mode;
-use std::ffi::CStr;
-use std::path::{Path, PathBuf};
+mod cli;
+mod colors;
+mod json;
+
+pub use cli::Ls;
+
+use colors::Colors;
+use nix::dir::{Dir, Type};
+use nix::errno::Errno;
+use nix::fcntl::{AtFlags, OFlag};
+use nix::sys::stat::{FileStat, Mode};
+use nix::unistd::{Gid, Group, Uid, User};
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use std::ffi::{CStr, CString, OsStr, OsString};
 use std::mem::forget;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+use std::io::Write;
+use crate::datetime;
+use crate::error::{Error, Operation};
+use crate::quote::{self, QuotingStyle};
+use crate::size::SizeFormat;
+use crate::ExitCode;
 
 pub struct Args<'a> {
     pub paths: Vec<&'a Path>,
     pub all: bool,
     pub shallow_dirs: bool,
+    pub long: bool,
+    /// `None` sorts by name, unless a time other than mtime has been picked without the long
+    /// format to show it in
+    pub sort: Option<SortKey>,
+    pub reverse: bool,
+    pub recursive: bool,
+    /// How names are arranged when not using the long format. `None` picks columns when writing
+    /// to a terminal, and one name per line otherwise.
+    pub layout: Option<Layout>,
+    /// List names starting with '.', but not '.' and '..' themselves
+    pub almost_all: bool,
+    pub indicators: IndicatorStyle,
+    /// Units for both file sizes and allocated blocks, set by -h, --si and --block-size. `None`
+    /// uses the environment's defaults.
+    pub size_format: Option<SizeFormat>,
+    /// Count allocated blocks in kibibytes, regardless of the environment
+    pub kibibytes: bool,
+    /// Print the space allocated to each file
+    pub show_blocks: bool,
+    /// Print the inode number of each file
+    pub show_inode: bool,
+    /// Print user and group ids in the long format instead of looking up their names
+    pub numeric_ids: bool,
+    pub dereference: Dereference,
+    pub quoting: QuotingStyle,
+    /// Print '?' in place of non-printable characters in names. `None` only hides them when
+    /// writing to a terminal.
+    pub hide_control: Option<bool>,
+    pub color: ColorMode,
+    /// Print one JSON object per entry
+    pub json: bool,
+    /// End lines with NUL rather than newline
+    pub zero: bool,
+    pub time_field: TimeField,
+    /// `None` uses $TIME_STYLE, or the POSIX style if that isn't set
+    pub time_style: Option<TimeStyle>,
+}
+
+/// Which of a file's timestamps is shown by the long format and sorted on by -t
+#[derive(Clone, Copy, PartialEq)]
+pub enum TimeField {
+    Modification,
+    Access,
+    /// When the inode last changed, e.g. from a chmod
+    Change,
+    /// When the file was created, which only some kernels and filesystems know
+    Birth,
+}
+
+/// How timestamps are rendered in the long format
+pub enum TimeStyle {
+    /// `%b %e %H:%M` for recent files and `%b %e  %Y` for older ones, as POSIX describes
+    Posix,
+    /// `%Y-%m-%d %H:%M:%S.%N %z`
+    FullIso,
+    /// `%Y-%m-%d %H:%M`
+    LongIso,
+    /// `%m-%d %H:%M` for recent files and `%Y-%m-%d ` for older ones
+    Iso,
+    /// `strftime` formats for older and recent files
+    Format { old: String, recent: String },
+}
+
+impl TimeStyle {
+    /// Parses the argument to --time-style, returning `None` if it isn't a known style
+    pub fn parse(spec: &str) -> Option<TimeStyle> {
+        if let Some(format) = spec.strip_prefix('+') {
+            // a second format on its own line is used for recent files
+            let (old, recent) = match format.find('\n') {
+                Some(i) => (&format[..i], &format[i + 1..]),
+                None => (format, format),
+            };
+            return Some(TimeStyle::Format {
+                old: old.to_string(),
+                recent: recent.to_string(),
+            });
+        }
+
+        match spec {
+            "full-iso" => Some(TimeStyle::FullIso),
+            "long-iso" => Some(TimeStyle::LongIso),
+            "iso" => Some(TimeStyle::Iso),
+            "locale" => Some(TimeStyle::Posix),
+            // "posix-" styles only apply outside of the POSIX locale, and we always act like
+            // we're in it
+            _ if spec.starts_with("posix-") => Some(TimeStyle::Posix),
+            _ => None,
+        }
+    }
+}
+
+/// When to color names according to `LS_COLORS`
+#[derive(Clone, Copy, PartialEq)]
+pub enum ColorMode {
+    Never,
+    /// Only when writing to a terminal
+    Auto,
+    Always,
+}
+
+/// Which symlinks are followed to list what they point to, rather than the links themselves
+#[derive(Clone, Copy, PartialEq)]
+pub enum Dereference {
+    /// Command line operands that link to directories, unless -d, -F or -l are in use
+    DirectoryOperands,
+    /// Every command line operand
+    Operands,
+    /// Every operand and every directory entry
+    All,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Layout {
+    /// One name per line
+    OneLine,
+    /// Columns filled top to bottom, then left to right
+    Columns,
+    /// Columns filled left to right, then top to bottom
+    Across,
+}
+
+/// The order that entries are listed in within each group
+#[derive(Clone, Copy, PartialEq)]
+pub enum SortKey {
+    /// Byte order of the names, ignoring the locale
+    Name,
+    /// Newest modification time first
+    Time,
+    /// Largest first
+    Size,
+    /// Alphabetically by the text following the last '.'
+    Extension,
+    /// Whatever order the directory was read in
+    Unsorted,
+}
+
+/// Which characters get appended to names to show what type of file they are
+#[derive(Clone, Copy, PartialEq)]
+pub enum IndicatorStyle {
+    None,
+    /// Only append '/' to directories
+    Slash,
+    /// Append '/' to directories, '*' to executables, '@' to symlinks, '|' to FIFOs and '=' to
+    /// sockets
+    Classify,
 }
 
 #[derive(Clone, Copy)]
 struct PrintRules {
     print_hidden: bool,
+    /// Print the '.' and '..' entries. Only relevant if hidden files are being printed.
+    print_dot_dirs: bool,
+    long: bool,
+    sort: SortKey,
+    reverse: bool,
+    recursive: bool,
+    layout: Layout,
+    /// Width of the terminal, only used when laying out columns
+    line_width: usize,
+    indicators: IndicatorStyle,
+    show_blocks: bool,
+    show_inode: bool,
+    numeric_ids: bool,
+    /// Follow symlinks found in directories
+    follow_entries: bool,
+    quoting: QuotingStyle,
+    hide_control: bool,
+    /// `None` when names shouldn't be colored
+    colors: Option<&'static Colors>,
+    json: bool,
+    /// What's printed at the end of each line
+    line_end: char,
+    time_field: TimeField,
+    /// Units for the size column of the long format
+    size_format: SizeFormat,
+    /// Units for allocated space, used by -s and the `total` line
+    block_format: SizeFormat,
 }
 
-pub fn main(args: Args) -> i32 {
-    // These declarations hoist the lifetime of the backing array for the default paths.
-    // Essentially, the memory for the default paths is always allocated, but only conditionally
-    // used. The type annotations are just there for clarity.
-    let cwd: PathBuf;
-    let default_paths: [&Path; 1];
+impl PrintRules {
+    /// Whether or not each entry needs to be stat'ed before it can be sorted and printed
+    fn needs_stat(&self) -> bool {
+        self.long
+            || self.json
+            || self.show_blocks
+            || self.sort == SortKey::Time
+            || self.sort == SortKey::Size
+    }
 
-    // if we weren't provided any directories to list out, point a slice towards a default array
-    // containing the current working directory. loop through that instead
-    let paths = if args.paths.is_empty() {
-        match std::env::current_dir() {
-            Ok(d) => cwd = d,
-            Err(e) => {
-                eprintln!("Unable to determine current directory: {}", e);
-                return crate::EXIT_CODE_NO_CWD;
-            }
+    /// Whether or not an entry needs to be stat'ed when readdir already told us its type, or
+    /// `None` if it didn't
+    fn needs_stat_with_type(&self, file_type: Option<Type>) -> bool {
+        // colors depend on permission bits, which are only in the mode
+        if self.needs_stat() || self.colors.is_some() {
+            return true;
+        }
+        match file_type {
+            // -R needs to know which entries are directories, and -F/-p need every type
+            None => self.recursive || self.indicators != IndicatorStyle::None,
+            // whether or not a regular file is executable is only in the mode
+            Some(Type::File) => self.indicators == IndicatorStyle::Classify,
+            // readdir describes the link itself, not what it points to
+            Some(Type::Symlink) => self.follow_entries,
+            Some(_) => false,
+        }
+    }
+}
+
+/// A single name to be listed, along with its metadata if the print rules call for it
+struct Entry {
+    name: OsString,
+    /// The type reported by readdir, which not every filesystem provides
+    file_type: Option<Type>,
+    /// The inode number reported by readdir
+    ino: Option<u64>,
+    stat: Option<FileStat>,
+    /// Where the entry points, if it's a symlink and the long format is in use
+    link_target: Option<OsString>,
+    /// Whether the entry is a symlink that doesn't point to anything. Only checked when coloring.
+    dangling: bool,
+    /// Seconds and nanoseconds since the epoch of when the file was created. Only checked when
+    /// that time has been asked for, and only if the kernel and filesystem know it.
+    birth: Option<(i64, i64)>,
+}
+
+impl Entry {
+    /// The type of file, or `None` if neither readdir nor a stat have told us
+    fn kind(&self) -> Option<Type> {
+        // the stat takes priority since it may have followed a symlink that readdir reported
+        match (self.stat.as_ref(), self.file_type) {
+            (Some(stat), _) => type_from_mode(stat.st_mode),
+            (None, file_type) => file_type,
+        }
+    }
+
+    fn inode(&self) -> u64 {
+        match (self.ino, self.stat.as_ref()) {
+            // readdir's inode is of the link itself, not of whatever -L followed it to
+            (Some(_), Some(stat)) if self.file_type == Some(Type::Symlink) => stat.st_ino,
+            (Some(ino), _) => ino,
+            (None, _) => stat_of(self).st_ino,
+        }
+    }
+
+    /// The requested timestamp as seconds and nanoseconds since the epoch
+    fn time(&self, field: TimeField) -> Option<(i64, i64)> {
+        let stat = stat_of(self);
+        match field {
+            TimeField::Modification => Some((stat.st_mtime, stat.st_mtime_nsec)),
+            TimeField::Access => Some((stat.st_atime, stat.st_atime_nsec)),
+            TimeField::Change => Some((stat.st_ctime, stat.st_ctime_nsec)),
+            TimeField::Birth => self.birth,
         }
-        default_paths = [cwd.as_path()];
+    }
+
+    fn is_dir(&self) -> bool {
+        self.kind() == Some(Type::Directory)
+    }
+
+    /// The character used by -F and -p to show the file's type
+    fn indicator(&self, style: IndicatorStyle) -> Option<char> {
+        let kind = self.kind()?;
+        match style {
+            IndicatorStyle::None => None,
+            IndicatorStyle::Slash if kind == Type::Directory => Some('/'),
+            IndicatorStyle::Slash => None,
+            IndicatorStyle::Classify => match kind {
+                Type::Directory => Some('/'),
+                Type::Symlink => Some('@'),
+                Type::Fifo => Some('|'),
+                Type::Socket => Some('='),
+                Type::File => {
+                    let exec_bits = libc::S_IXUSR | libc::S_IXGRP | libc::S_IXOTH;
+                    match self.stat.as_ref() {
+                        Some(stat) if stat.st_mode & exec_bits != 0 => Some('*'),
+                        _ => None,
+                    }
+                }
+                Type::CharacterDevice | Type::BlockDevice => None,
+            },
+        }
+    }
+}
+
+fn type_from_mode(mode: libc::mode_t) -> Option<Type> {
+    match mode & libc::S_IFMT {
+        libc::S_IFIFO => Some(Type::Fifo),
+        libc::S_IFCHR => Some(Type::CharacterDevice),
+        libc::S_IFDIR => Some(Type::Directory),
+        libc::S_IFBLK => Some(Type::BlockDevice),
+        libc::S_IFREG => Some(Type::File),
+        libc::S_IFLNK => Some(Type::Symlink),
+        libc::S_IFSOCK => Some(Type::Socket),
+        _ => None,
+    }
+}
+
+pub fn main(args: Args) -> ExitCode {
+    // if we weren't provided any directories to list out, list the current one. It's named
+    // relatively so that the output doesn't depend on where the tree is.
+    let default_paths = [Path::new(".")];
+    let paths = if args.paths.is_empty() {
         &default_paths[..]
     } else {
         &args.paths[..]
     };
 
+    let stdout_is_tty = nix::unistd::isatty(libc::STDOUT_FILENO).unwrap_or(false);
+    let layout = match args.layout {
+        // the point of NUL separators is to split names exactly, which columns would get in the
+        // way of
+        _ if args.zero => Layout::OneLine,
+        Some(layout) => layout,
+        None if stdout_is_tty => Layout::Columns,
+        None => Layout::OneLine,
+    };
+
+    // an explicit unit applies to everything, but the environment is only consulted for what the
+    // flags don't cover
+    let env_format = SizeFormat::from_env(&["LS_BLOCK_SIZE", "BLOCK_SIZE"]);
+    let size_format = args.size_format.or(env_format).unwrap_or(SizeFormat::BYTES);
+    let block_format = match (args.size_format, args.kibibytes, env_format) {
+        (Some(format), _, _) => format,
+        (None, true, _) => SizeFormat::Units { unit: 1024, suffix: "" },
+        (None, false, Some(format)) => format,
+        // POSIX says blocks are 512 bytes, but nobody's counted that way in a long time
+        (None, false, None) if std::env::var_os("POSIXLY_CORRECT").is_some() => {
+            SizeFormat::Units { unit: 512, suffix: "" }
+        }
+        (None, false, None) => SizeFormat::Units { unit: 1024, suffix: "" },
+    };
+
+    let long = args.long || args.numeric_ids;
+    let sort = match args.sort {
+        Some(sort) => sort,
+        // without the long format to show them in, -u and -c are only useful for sorting
+        None if args.time_field != TimeField::Modification && !long => SortKey::Time,
+        None => SortKey::Name,
+    };
+
+    let time_style = args.time_style.unwrap_or_else(|| {
+        std::env::var("TIME_STYLE")
+            .ok()
+            .and_then(|spec| TimeStyle::parse(&spec))
+            .unwrap_or(TimeStyle::Posix)
+    });
+
+    let use_colors = match args.color {
+        _ if args.json => false,
+        ColorMode::Never => false,
+        ColorMode::Auto => stdout_is_tty,
+        ColorMode::Always => true,
+    };
+    // leaked so that the print rules can stay Copy. The program is short-lived anyways.
+    let colors: Option<&'static Colors> = if use_colors {
+        Some(Box::leak(Box::new(Colors::from_env())))
+    } else {
+        None
+    };
+
     let print_rules = PrintRules {
-        print_hidden: args.all,
+        print_hidden: args.all || args.almost_all,
+        print_dot_dirs: args.all,
+        // -n is its own long format
+        long,
+        sort,
+        reverse: args.reverse,
+        recursive: args.recursive,
+        layout,
+        line_width: if layout == Layout::OneLine { 0 } else { terminal_width() },
+        indicators: args.indicators,
+        show_blocks: args.show_blocks,
+        show_inode: args.show_inode,
+        numeric_ids: args.numeric_ids,
+        follow_entries: args.dereference == Dereference::All,
+        // names have to come out exactly as they are for NUL separators and JSON to be useful
+        quoting: if args.zero || args.json { QuotingStyle::Literal } else { args.quoting },
+        hide_control: !args.zero && !args.json && args.hide_control.unwrap_or(stdout_is_tty),
+        colors,
+        json: args.json,
+        line_end: if args.zero { '\0' } else { '\n' },
+        time_field: args.time_field,
+        size_format,
+        block_format,
     };
 
-    let mut status = 0;
+    // POSIX only has ls look through symlinks given on the command line when listing their
+    // contents is the obvious thing to do. Anything that looks at the file itself sees the link.
+    let follow_operands = match args.dereference {
+        Dereference::All | Dereference::Operands => true,
+        Dereference::DirectoryOperands => {
+            let classify = print_rules.indicators == IndicatorStyle::Classify;
+            !(args.shallow_dirs || print_rules.long || classify)
+        }
+    };
 
+    let mut status = ExitCode::Success;
     let mut print_shallow = Vec::new(); // print just the provided path, used for testing existence
     let mut print_contents = Vec::new(); // print directory contents
 
     // print files first
     for &fpath in paths {
-        if !fpath.exists() {
-            eprintln!("'{}': No such file or directory", fpath.display());
-        } else if !fpath.is_dir() || args.shallow_dirs {
-            print_shallow.push(fpath);
+        // arguments can't contain NUL, so this never falls back to the empty path
+        let cpath = CString::new(fpath.as_os_str().as_bytes()).unwrap_or_default();
+        let mut fail = |op, e| status = status.max(Error::new(op, fpath, e).report("ls"));
+        let follow = follow_operands;
+        let entry = match load_entry(libc::AT_FDCWD, &cpath, true, follow, print_rules, &mut fail) {
+            Some(entry) => entry,
+            None => continue,
+        };
+
+        if !entry.is_dir() || args.shallow_dirs {
+            print_shallow.push(entry);
         } else { // this is an existing directory
             print_contents.push(fpath);
         }
     }
 
-    for &fpath in print_shallow.iter() {
-        println!("{}", fpath.display());
-    }
+    let mut lister = Lister {
+        print_rules,
+        long_format: LongFormat::new(time_style),
+        status,
+        group_spacing: !print_shallow.is_empty(),
+        // -R always labels its groups since there's almost always going to be more than one
+        label_dir_groups: !print_shallow.is_empty() || print_contents.len() > 1 || args.recursive,
+        active_dirs: HashSet::new(),
+    };
 
-    let mut group_spacing = !print_shallow.is_empty();
-    let label_dir_groups = group_spacing || print_contents.len() > 1;
+    sort_entries(&mut print_shallow, print_rules);
+    print_entries(&print_shallow, None, print_rules, &mut lister.long_format);
 
     for &dpath in print_contents.iter() {
-        let dir = nix::dir::Dir::open(dpath, OFlag::O_RDONLY, Mode::empty());
+        lister.list_dir(dpath);
+    }
+    let status = lister.status;
+
+    // the program is short-lived and the OS is gonna clean up after us anyways
+    forget(print_shallow);
+    forget(print_contents);
+
+    return status;
+}
+
+/// Everything needed to print each directory group, potentially recursing into subdirectories
+struct Lister {
+    print_rules: PrintRules,
+    long_format: LongFormat,
+    status: ExitCode,
+    /// Whether a blank line is needed before the next group to separate it from the last
+    group_spacing: bool,
+    label_dir_groups: bool,
+    /// The (st_dev, st_ino) of every directory that -R is in the middle of listing. Coming across
+    /// one of these again means a symlink or bind mount has created a loop.
+    active_dirs: HashSet<(libc::dev_t, libc::ino_t)>,
+}
+
+impl Lister {
+    /// Reports an error, and keeps going with whatever else can still be listed
+    fn fail(&mut self, e: Error) {
+        self.status = self.status.max(e.report("ls"));
+    }
+
+    fn list_dir(&mut self, dpath: &Path) {
+        let print_rules = self.print_rules;
+
+        let dir = Dir::open(dpath, OFlag::O_RDONLY, Mode::empty());
         let mut dir = match dir {
             Ok(dir) => dir,
             Err(e) => {
-                eprintln!("{:?}: {}", dpath, e);
-                status = crate::EXIT_CODE_READ_DIR;
-                continue;
+                self.fail(Error::new(Operation::ReadDir, dpath, e));
+                return;
             }
         };
 
-        if group_spacing {
-            println!()
+        // the fd is copied out so that entries can be stat'ed relative to the directory while
+        // the iterator holds the mutable borrow
+        let dirfd = dir.as_raw_fd();
+
+        let dir_id = if print_rules.recursive {
+            let dir_stat = match nix::sys::stat::fstat(dirfd) {
+                Ok(s) => s,
+                Err(e) => {
+                    self.fail(Error::new(Operation::Stat, dpath, e));
+                    return;
+                }
+            };
+            let dir_id = (dir_stat.st_dev, dir_stat.st_ino);
+            if !self.active_dirs.insert(dir_id) {
+                // a directory that contains itself, through a symlink or a bind mount
+                self.fail(Error::new(Operation::ReadDir, dpath, nix::Error::Sys(Errno::ELOOP)));
+                return;
+            }
+            Some(dir_id)
+        } else {
+            None
+        };
+
+        // every JSON object carries its own path, so there's nothing to separate
+        if self.group_spacing && !print_rules.json {
+            end_line(print_rules);
         }
-        if label_dir_groups {
-            println!("{}:", dpath.display());
+        if self.label_dir_groups && !print_rules.json {
+            print_name(dpath.as_os_str(), print_rules);
+            print!(":");
+            end_line(print_rules);
         }
 
+        let mut entries = Vec::new();
         for entry in dir.iter() {
-            match entry {
-                Ok(entry) => maybe_print_entry(&entry.file_name(), print_rules),
-                Err(e) => eprintln!("Error reading {}: {}", dpath.display(), e),
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(e) => {
+                    self.fail(Error::new(Operation::ReadDir, dpath, e));
+                    continue;
+                }
+            };
+
+            let name = entry.file_name();
+            if !print_rules.print_hidden && entry_is_hidden(name) {
+                continue;
             }
+            if !print_rules.print_dot_dirs && entry_is_dot_dir(name) {
+                continue;
+            }
+
+            let file_type = entry.file_type();
+            let needs_stat = print_rules.needs_stat_with_type(file_type);
+            let follow = print_rules.follow_entries;
+            let mut fail = |op, e| {
+                let fpath = dpath.join(OsStr::from_bytes(name.to_bytes()));
+                self.fail(Error::new(op, &fpath, e));
+            };
+            let loaded = load_entry(dirfd, name, needs_stat, follow, print_rules, &mut fail);
+            if let Some(loaded) = loaded {
+                entries.push(Entry { file_type, ino: Some(entry.ino()), ..loaded });
+            }
+        }
+        // don't hold onto a file descriptor for every level of the tree while recursing
+        drop(dir);
+
+        if (print_rules.long || print_rules.show_blocks) && !print_rules.json {
+            print!("total {}", print_rules.block_format.format(total_allocated(&entries)));
+            end_line(print_rules);
         }
+        sort_entries(&mut entries, print_rules);
+        print_entries(&entries, Some(dpath), print_rules, &mut self.long_format);
+
+        self.group_spacing = true;
 
-        group_spacing = true;
+        if let Some(dir_id) = dir_id {
+            for entry in entries.iter() {
+                let name = entry.name.as_bytes();
+                if entry.is_dir() && name != b"." && name != b".." {
+                    self.list_dir(&dpath.join(&entry.name));
+                }
+            }
+            self.active_dirs.remove(&dir_id);
+        }
     }
+}
 
-    // the program is short-lived and the OS is gonna clean up after us anyways
-    forget(print_shallow);
-    forget(print_contents);
+fn sort_entries(entries: &mut [Entry], print_rules: PrintRules) {
+    // -r doesn't apply when there's no order to reverse
+    if print_rules.sort == SortKey::Unsorted {
+        return;
+    }
 
-    return status;
+    // the sort is stable, so reversing afterwards also reverses the order of ties, just like
+    // negating the comparison would
+    entries.sort_by(|a, b| compare_entries(a, b, print_rules));
+    if print_rules.reverse {
+        entries.reverse();
+    }
+}
+
+fn compare_entries(a: &Entry, b: &Entry, print_rules: PrintRules) -> Ordering {
+    let by_key = match print_rules.sort {
+        // newest first. Files without the time (e.g. no birth time) come after everything else
+        SortKey::Time => b.time(print_rules.time_field).cmp(&a.time(print_rules.time_field)),
+        // largest first
+        SortKey::Size => stat_of(b).st_size.cmp(&stat_of(a).st_size),
+        SortKey::Extension => extension(&a.name).cmp(extension(&b.name)),
+        SortKey::Name | SortKey::Unsorted => Ordering::Equal,
+    };
+    by_key.then_with(|| compare_names(&a.name, &b.name))
+}
+
+/// Names are compared byte-wise rather than with the locale's collation rules so that listings
+/// are the same on every system
+fn compare_names(a: &OsStr, b: &OsStr) -> Ordering {
+    a.as_bytes().cmp(b.as_bytes())
 }
 
-fn maybe_print_entry(entry: &CStr, print_rules: PrintRules) {
-    if !print_rules.print_hidden && entry_is_hidden(&entry) {
+/// The stat of an entry that's being sorted on its metadata. `PrintRules::needs_stat` guarantees
+/// that this is always present for those sort keys.
+fn stat_of(entry: &Entry) -> &FileStat {
+    entry.stat.as_ref().expect("entry wasn't stat'ed before sorting")
+}
+
+/// Everything from the last '.' onward, or nothing if there's no '.' in the name
+fn extension(name: &OsStr) -> &[u8] {
+    let name = name.as_bytes();
+    match name.iter().rposition(|&b| b == b'.') {
+        Some(i) => &name[i..],
+        None => &[],
+    }
+}
+
+/// Prints a group of entries. `dir` is the directory they were read from, or `None` if they came
+/// from the command line.
+fn print_entries(
+    entries: &[Entry],
+    dir: Option<&Path>,
+    print_rules: PrintRules,
+    long_format: &mut LongFormat,
+) {
+    if print_rules.json {
+        for entry in entries {
+            json::print_entry(entry, dir);
+            end_line(print_rules);
+        }
         return;
     }
-    // TODO: display CStr without dynamically allocating
-    println!("{}", entry.to_string_lossy())
+
+    if print_rules.long {
+        long_format.print(entries, print_rules);
+        return;
+    }
+
+    let prefix_widths = PrefixWidths::of(entries, print_rules);
+    match print_rules.layout {
+        Layout::OneLine => {
+            for entry in entries {
+                prefix_widths.print(entry, print_rules);
+                print_entry(entry, print_rules);
+                end_line(print_rules);
+            }
+        }
+        Layout::Columns => print_columns(entries, false, print_rules, prefix_widths),
+        Layout::Across => print_columns(entries, true, print_rules, prefix_widths),
+    }
+}
+
+/// The space allocated to an entry, formatted for -s
+fn allocated_string(entry: &Entry, print_rules: PrintRules) -> String {
+    let stat = stat_of(entry);
+    // st_blocks is always in 512-byte units, regardless of the filesystem's block size
+    print_rules.block_format.format(stat.st_blocks as u64 * 512)
+}
+
+/// Widths of the optional columns printed before each name, sized so that every entry in a group
+/// lines up. A width of 0 means the column isn't shown at all.
+#[derive(Clone, Copy)]
+struct PrefixWidths {
+    /// -i
+    inode: usize,
+    /// -s
+    blocks: usize,
+}
+
+impl PrefixWidths {
+    fn of(entries: &[Entry], print_rules: PrintRules) -> Self {
+        let mut widths = PrefixWidths { inode: 0, blocks: 0 };
+        for entry in entries {
+            if print_rules.show_inode {
+                widths.inode = widths.inode.max(entry.inode().to_string().len());
+            }
+            if print_rules.show_blocks {
+                widths.blocks = widths.blocks.max(allocated_string(entry, print_rules).len());
+            }
+        }
+        widths
+    }
+
+    /// Number of terminal columns taken up by `print`
+    fn total(&self) -> usize {
+        // each column is followed by a space
+        let column = |width| if width > 0 { width + 1 } else { 0 };
+        column(self.inode) + column(self.blocks)
+    }
+
+    fn print(&self, entry: &Entry, print_rules: PrintRules) {
+        if print_rules.show_inode {
+            print!("{:>1$} ", entry.inode(), self.inode);
+        }
+        if print_rules.show_blocks {
+            print!("{:>1$} ", allocated_string(entry, print_rules), self.blocks);
+        }
+    }
+}
+
+/// Lays names out in as many columns as will fit in `line_width`, with each column only as wide
+/// as its longest name
+fn print_columns(
+    entries: &[Entry],
+    across: bool,
+    print_rules: PrintRules,
+    prefix_widths: PrefixWidths,
+) {
+    const GAP: usize = 2; // spaces between columns
+
+    if entries.is_empty() {
+        return;
+    }
+
+    let line_width = print_rules.line_width;
+    let widths: Vec<usize> = entries
+        .iter()
+        .map(|e| prefix_widths.total() + entry_width(e, print_rules))
+        .collect();
+    let count = widths.len();
+
+    // the narrowest a column could possibly be is a single character plus the gap, so there's no
+    // point trying more columns than that
+    let max_cols = (line_width / (1 + GAP)).max(1).min(count);
+
+    let mut col_widths = Vec::with_capacity(max_cols);
+    let mut rows = count;
+    let mut cols = 1;
+    for try_cols in (1..=max_cols).rev() {
+        rows = count.div_ceil(try_cols);
+        // filling top to bottom may leave the last columns empty, e.g. 4 names in 3 columns
+        // only needs 2 rows, which fills just 2 of the columns
+        cols = if across { try_cols } else { count.div_ceil(rows) };
+
+        col_widths.clear();
+        col_widths.resize(cols, 0);
+        for (i, &width) in widths.iter().enumerate() {
+            let col = if across { i % cols } else { i / rows };
+            col_widths[col] = col_widths[col].max(width);
+        }
+
+        let total: usize = col_widths.iter().sum::<usize>() + GAP * (cols - 1);
+        if total <= line_width {
+            break;
+        }
+    }
+
+    for row in 0..rows {
+        for (col, &col_width) in col_widths.iter().enumerate() {
+            let i = if across { row * cols + col } else { col * rows + row };
+            if i >= count {
+                break;
+            }
+            prefix_widths.print(&entries[i], print_rules);
+            print_entry(&entries[i], print_rules);
+
+            let next = if across { i + 1 } else { i + rows };
+            let last_in_row = col + 1 == cols || next >= count;
+            if !last_in_row {
+                print!("{:1$}", "", col_width - widths[i] + GAP);
+            }
+        }
+        end_line(print_rules);
+    }
+}
+
+/// Number of terminal columns taken up by `print_entry`
+fn entry_width(entry: &Entry, print_rules: PrintRules) -> usize {
+    let indicator_width = entry.indicator(print_rules.indicators).is_some() as usize;
+    let name = quote::quote(entry.name.as_bytes(), print_rules.quoting, print_rules.hide_control);
+    // anything that isn't valid UTF-8 will have been escaped or replaced if it's going to a
+    // terminal, so this only really needs to be accurate for valid names
+    String::from_utf8_lossy(&name).chars().count() + indicator_width
+}
+
+/// Prints the entry's name and type indicator without a trailing newline
+fn print_entry(entry: &Entry, print_rules: PrintRules) {
+    match print_rules.colors.and_then(|c| c.code_for(entry)) {
+        Some(code) => {
+            print!("\x1b[{}m", code);
+            print_name(&entry.name, print_rules);
+            print!("\x1b[0m");
+        }
+        None => print_name(&entry.name, print_rules),
+    }
+    if let Some(indicator) = entry.indicator(print_rules.indicators) {
+        print!("{}", indicator);
+    }
+}
+
+fn end_line(print_rules: PrintRules) {
+    print!("{}", print_rules.line_end);
+}
+
+/// Prints a name, quoted according to the print rules, without a trailing newline. The raw bytes
+/// are written so that names that aren't UTF-8 come out exactly as they are on disk.
+fn print_name(name: &OsStr, print_rules: PrintRules) {
+    let name = quote::quote(name.as_bytes(), print_rules.quoting, print_rules.hide_control);
+    // print!() goes through the same buffer, so the output stays in order. Failing to write to
+    // stdout isn't something that can be reported anywhere useful.
+    let _ = std::io::stdout().write_all(&name);
+}
+
+/// Width of the terminal attached to stdout, falling back to $COLUMNS and then the traditional 80
+/// when stdout isn't a terminal
+fn terminal_width() -> usize {
+    let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+    let result = unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) };
+    if result == 0 && size.ws_col > 0 {
+        return size.ws_col as usize;
+    }
+
+    std::env::var("COLUMNS")
+        .ok()
+        .and_then(|c| c.parse().ok())
+        .filter(|&c| c > 0)
+        .unwrap_or(80)
 }
 
 fn entry_is_hidden(entry_name: &CStr) -> bool {
@@ -117,3 +848,319 @@ fn entry_is_hidden(entry_name: &CStr) -> bool {
     // doesn't consider the hidden bit flag that exists on some filesystems (FAT, ntfs)
     entry_name.to_bytes()[0] == b'.'
 }
+
+fn entry_is_dot_dir(entry_name: &CStr) -> bool {
+    let name = entry_name.to_bytes();
+    name == b"." || name == b".."
+}
+
+/// Looks up what the print rules need to know about `name`, relative to `dirfd`. `stat` decides
+/// whether it gets stat'ed at all, and `follow` whether that follows symlinks. Problems are passed
+/// to `fail`, and `None` is returned if there's nothing that can be listed.
+fn load_entry(
+    dirfd: RawFd,
+    name: &CStr,
+    stat: bool,
+    follow: bool,
+    print_rules: PrintRules,
+    fail: &mut dyn FnMut(Operation, nix::Error),
+) -> Option<Entry> {
+    let stat = if stat {
+        let result = if follow {
+            match nix::sys::stat::fstatat(dirfd, name, AtFlags::empty()) {
+                // a dangling symlink is still something that can be listed
+                Err(nix::Error::Sys(Errno::ENOENT)) => {
+                    nix::sys::stat::fstatat(dirfd, name, AtFlags::AT_SYMLINK_NOFOLLOW)
+                }
+                result => result,
+            }
+        } else {
+            nix::sys::stat::fstatat(dirfd, name, AtFlags::AT_SYMLINK_NOFOLLOW)
+        };
+        match result {
+            Ok(s) => Some(s),
+            Err(e) => {
+                fail(Operation::Stat, e);
+                return None;
+            }
+        }
+    } else {
+        None
+    };
+
+    let is_link = stat.map(|s| s.st_mode & libc::S_IFMT == libc::S_IFLNK);
+    let link_target = if (print_rules.long || print_rules.json) && is_link == Some(true) {
+        match nix::fcntl::readlinkat(dirfd, name) {
+            Ok(target) => Some(target),
+            Err(e) => {
+                fail(Operation::ReadLink, e);
+                None
+            }
+        }
+    } else {
+        None
+    };
+
+    // the link's own stat doesn't say whether it points anywhere
+    let dangling = print_rules.colors.is_some()
+        && is_link == Some(true)
+        && nix::sys::stat::fstatat(dirfd, name, AtFlags::empty()).is_err();
+    let birth = if print_rules.time_field == TimeField::Birth {
+        // stick with whatever the stat decided to follow
+        birth_time(dirfd, name, is_link != Some(true))
+    } else {
+        None
+    };
+
+    Some(Entry {
+        name: OsStr::from_bytes(name.to_bytes()).to_os_string(),
+        file_type: None,
+        ino: None,
+        stat,
+        link_target,
+        dangling,
+        birth,
+    })
+}
+
+/// Looks up when a file was created, relative to `dirfd`. Returns `None` if the kernel or
+/// filesystem doesn't keep track of it, which is common enough to not be worth reporting.
+#[cfg(target_os = "linux")]
+fn birth_time(dirfd: RawFd, name: &CStr, follow: bool) -> Option<(i64, i64)> {
+    let flags = if follow { 0 } else { libc::AT_SYMLINK_NOFOLLOW };
+    let mut stx: libc::statx = unsafe { std::mem::zeroed() };
+    let result = unsafe { libc::statx(dirfd, name.as_ptr(), flags, libc::STATX_BTIME, &mut stx) };
+    if result != 0 || stx.stx_mask & libc::STATX_BTIME == 0 {
+        return None;
+    }
+    Some((stx.stx_btime.tv_sec, stx.stx_btime.tv_nsec as i64))
+}
+
+/// Only Linux has `statx`, so birth times are unknown everywhere else
+#[cfg(not(target_os = "linux"))]
+fn birth_time(_dirfd: RawFd, _name: &CStr, _follow: bool) -> Option<(i64, i64)> {
+    None
+}
+
+/// Sum of the space allocated to every entry, in bytes. This is what gets printed on the `total`
+/// line of a long listing.
+fn total_allocated(entries: &[Entry]) -> u64 {
+    // st_blocks is always in 512-byte units, regardless of the filesystem's block size
+    let blocks: i64 = entries
+        .iter()
+        .filter_map(|e| e.stat.as_ref())
+        .map(|s| s.st_blocks)
+        .sum();
+    blocks as u64 * 512
+}
+
+/// State shared between every group printed in the long format. Looking up user and group names
+/// can mean reading and parsing /etc/passwd each time, so names are cached across groups.
+struct LongFormat {
+    users: HashMap<libc::uid_t, String>,
+    groups: HashMap<libc::gid_t, String>,
+    time_style: TimeStyle,
+    now: i64,
+}
+
+/// The columns of a single line of long output, formatted ahead of time so that the widths of
+/// every column can be known before anything is printed
+struct LongRow<'a> {
+    mode: [u8; 10],
+    nlink: String,
+    owner: String,
+    group: String,
+    size: String,
+    time: String,
+    entry: &'a Entry,
+}
+
+impl LongFormat {
+    fn new(time_style: TimeStyle) -> Self {
+        datetime::init_timezone();
+
+        let now = match SystemTime::now().duration_since(UNIX_EPOCH) {
+            Ok(d) => d.as_secs() as i64,
+            Err(_) => 0,
+        };
+
+        Self {
+            users: HashMap::new(),
+            groups: HashMap::new(),
+            time_style,
+            now,
+        }
+    }
+
+    fn user_name(&mut self, uid: libc::uid_t) -> String {
+        self.users
+            .entry(uid)
+            .or_insert_with(|| match User::from_uid(Uid::from_raw(uid)) {
+                Ok(Some(user)) => user.name,
+                // fall back to the number for ids that don't have a database entry
+                _ => uid.to_string(),
+            })
+            .clone()
+    }
+
+    fn group_name(&mut self, gid: libc::gid_t) -> String {
+        self.groups
+            .entry(gid)
+            .or_insert_with(|| match Group::from_gid(Gid::from_raw(gid)) {
+                Ok(Some(group)) => group.name,
+                _ => gid.to_string(),
+            })
+            .clone()
+    }
+
+    /// Renders a timestamp according to the time style, or "-" if it isn't known
+    fn format_time(&self, time: Option<(i64, i64)>) -> String {
+        const SIX_MONTHS: i64 = 365 * 24 * 60 * 60 / 2;
+
+        let (secs, nsec) = match time {
+            Some(time) => time,
+            None => return "-".to_string(),
+        };
+        let recent = secs <= self.now && self.now - secs <= SIX_MONTHS;
+
+        let format = match &self.time_style {
+            TimeStyle::Posix if recent => "%b %e %H:%M",
+            TimeStyle::Posix => "%b %e  %Y",
+            TimeStyle::FullIso => "%Y-%m-%d %H:%M:%S.%N %z",
+            TimeStyle::LongIso => "%Y-%m-%d %H:%M",
+            TimeStyle::Iso if recent => "%m-%d %H:%M",
+            TimeStyle::Iso => "%Y-%m-%d ",
+            TimeStyle::Format { recent: format, .. } if recent => format,
+            TimeStyle::Format { old: format, .. } => format,
+        };
+        datetime::format(format, &datetime::local_time(secs), nsec)
+    }
+
+    fn print(&mut self, entries: &[Entry], print_rules: PrintRules) {
+        let mut rows = Vec::with_capacity(entries.len());
+        for entry in entries {
+            let stat = match entry.stat.as_ref() {
+                Some(s) => s,
+                None => continue,
+            };
+
+            let file_type = stat.st_mode & libc::S_IFMT;
+            let size = if file_type == libc::S_IFCHR || file_type == libc::S_IFBLK {
+                // devices don't have a size, so show which device they are instead
+                let (major, minor) = device_numbers(stat.st_rdev);
+                format!("{}, {}", major, minor)
+            } else {
+                print_rules.size_format.format(stat.st_size as u64)
+            };
+
+            rows.push(LongRow {
+                mode: mode_string(stat.st_mode),
+                nlink: stat.st_nlink.to_string(),
+                owner: if print_rules.numeric_ids {
+                    stat.st_uid.to_string()
+                } else {
+                    self.user_name(stat.st_uid)
+                },
+                group: if print_rules.numeric_ids {
+                    stat.st_gid.to_string()
+                } else {
+                    self.group_name(stat.st_gid)
+                },
+                size,
+                time: self.format_time(entry.time(print_rules.time_field)),
+                entry,
+            });
+        }
+
+        let prefix_widths = PrefixWidths::of(entries, print_rules);
+        let nlink_width = rows.iter().map(|r| r.nlink.len()).max().unwrap_or(0);
+        let owner_width = rows.iter().map(|r| r.owner.len()).max().unwrap_or(0);
+        let group_width = rows.iter().map(|r| r.group.len()).max().unwrap_or(0);
+        let size_width = rows.iter().map(|r| r.size.len()).max().unwrap_or(0);
+        let time_width = rows.iter().map(|r| r.time.chars().count()).max().unwrap_or(0);
+
+        for row in rows.iter() {
+            prefix_widths.print(row.entry, print_rules);
+            print!(
+                "{} {:>nlink_width$} {:<owner_width$} {:<group_width$} {:>size_width$} {:<time_width$} ",
+                // mode_string only ever produces ascii
+                String::from_utf8_lossy(&row.mode),
+                row.nlink,
+                row.owner,
+                row.group,
+                row.size,
+                row.time,
+                nlink_width = nlink_width,
+                owner_width = owner_width,
+                group_width = group_width,
+                size_width = size_width,
+                time_width = time_width,
+            );
+            match row.entry.link_target.as_ref() {
+                Some(target) => {
+                    // the indicator belongs to the target, not the link
+                    let link_rules = PrintRules {
+                        indicators: IndicatorStyle::None,
+                        ..print_rules
+                    };
+                    print_entry(row.entry, link_rules);
+                    print!(" -> ");
+                    print_name(target, print_rules);
+                }
+                None => print_entry(row.entry, print_rules),
+            }
+            end_line(print_rules);
+        }
+    }
+}
+
+/// The major and minor numbers of a device. How they're packed into `st_rdev` is up to each OS.
+#[cfg(target_os = "linux")]
+fn device_numbers(rdev: libc::dev_t) -> (u64, u64) {
+    (nix::sys::stat::major(rdev), nix::sys::stat::minor(rdev))
+}
+
+/// The major and minor numbers of a device, packed the way macOS and the BSDs before FreeBSD 12
+/// do it
+#[cfg(not(target_os = "linux"))]
+fn device_numbers(rdev: libc::dev_t) -> (u64, u64) {
+    let rdev = rdev as u64;
+    ((rdev >> 24) & 0xff, rdev & 0xff_ffff)
+}
+
+/// Renders the file type and permission bits the way `ls -l` does, e.g. `drwxr-xr-x`
+fn mode_string(mode: libc::mode_t) -> [u8; 10] {
+    let file_type = match mode & libc::S_IFMT {
+        libc::S_IFDIR => b'd',
+        libc::S_IFLNK => b'l',
+        libc::S_IFCHR => b'c',
+        libc::S_IFBLK => b'b',
+        libc::S_IFIFO => b'p',
+        libc::S_IFSOCK => b's',
+        _ => b'-',
+    };
+
+    // picks the character for an execute bit that may be overridden by a special bit
+    fn exec_char(mode: libc::mode_t, exec: libc::mode_t, special: libc::mode_t, set: u8) -> u8 {
+        match (mode & exec != 0, mode & special != 0) {
+            (true, true) => set,
+            (false, true) => set.to_ascii_uppercase(),
+            (true, false) => b'x',
+            (false, false) => b'-',
+        }
+    }
+    let bit = |mask: libc::mode_t, c: u8| if mode & mask != 0 { c } else { b'-' };
+
+    [
+        file_type,
+        bit(libc::S_IRUSR, b'r'),
+        bit(libc::S_IWUSR, b'w'),
+        exec_char(mode, libc::S_IXUSR, libc::S_ISUID, b's'),
+        bit(libc::S_IRGRP, b'r'),
+        bit(libc::S_IWGRP, b'w'),
+        exec_char(mode, libc::S_IXGRP, libc::S_ISGID, b's'),
+        bit(libc::S_IROTH, b'r'),
+        bit(libc::S_IWOTH, b'w'),
+        exec_char(mode, libc::S_IXOTH, libc::S_ISVTX, b't'),
+    ]
+}
@@ -0,0 +1,365 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! The command line definition of `ls`, and the conversion of its many overlapping flags into
+//! `Args`.
+
+use super::{
+    main, Args, ColorMode, Dereference, IndicatorStyle, Layout, SortKey, TimeField, TimeStyle,
+};
+use crate::applet::{self, last_present, Applet};
+use crate::error;
+use crate::quote::QuotingStyle;
+use crate::size::SizeFormat;
+use crate::ExitCode;
+use clap::{App, Arg, ArgMatches, SubCommand};
+use std::ffi::OsStr;
+
+pub struct Ls;
+
+impl Applet for Ls {
+    fn name(&self) -> &'static str {
+        "ls"
+    }
+
+    fn command(&self) -> App<'static, 'static> {
+        SubCommand::with_name("ls")
+            .about("List directory contents")
+            .args(&[
+                Arg::with_name("files")
+                    .takes_value(true)
+                    .multiple(true),
+                Arg::with_name("all")
+                    .short("a")
+                    .long("all")
+                    .multiple(true)
+                    .help("List hidden files"),
+                Arg::with_name("almost-all")
+                    .short("A")
+                    .long("almost-all")
+                    .multiple(true)
+                    .help("List hidden files, except for '.' and '..'"),
+                Arg::with_name("classify")
+                    .short("F")
+                    .long("classify")
+                    .multiple(true)
+                    .help("Append an indicator (one of */=@|) to entries"),
+                Arg::with_name("slash-dirs")
+                    .short("p")
+                    .multiple(true)
+                    .help("Append / to directories"),
+                Arg::with_name("human-readable")
+                    .short("h")
+                    .long("human-readable")
+                    .multiple(true)
+                    .help("Print sizes like 1K, 234M, 2G etc."),
+                Arg::with_name("si")
+                    .long("si")
+                    .multiple(true)
+                    .help("Like -h, but use powers of 1000, not 1024"),
+                Arg::with_name("block-size")
+                    .long("block-size")
+                    .takes_value(true)
+                    .number_of_values(1)
+                    .multiple(true)
+                    .value_name("SIZE")
+                    .help("Scale sizes by SIZE, e.g. '--block-size=M'"),
+                Arg::with_name("kibibytes")
+                    .short("k")
+                    .long("kibibytes")
+                    .help("Use 1024-byte blocks for allocated space"),
+                Arg::with_name("size")
+                    .short("s")
+                    .long("size")
+                    .help("Print the allocated size of each file, in blocks"),
+                Arg::with_name("inode")
+                    .short("i")
+                    .long("inode")
+                    .help("Print the inode number of each file"),
+                Arg::with_name("numeric-uid-gid")
+                    .short("n")
+                    .long("numeric-uid-gid")
+                    .help("Like -l, but list numeric user and group IDs"),
+                Arg::with_name("dereference")
+                    .short("L")
+                    .long("dereference")
+                    .multiple(true)
+                    .help("Show information about the files symlinks point to"),
+                Arg::with_name("dereference-command-line")
+                    .short("H")
+                    .long("dereference-command-line")
+                    .multiple(true)
+                    .help("Follow symlinks listed on the command line"),
+                Arg::with_name("escape")
+                    .short("b")
+                    .long("escape")
+                    .multiple(true)
+                    .help("Print C-style escapes for non-printable characters"),
+                Arg::with_name("literal")
+                    .short("N")
+                    .long("literal")
+                    .multiple(true)
+                    .help("Print names without quoting"),
+                Arg::with_name("quote-name")
+                    .short("Q")
+                    .long("quote-name")
+                    .multiple(true)
+                    .help("Enclose names in double quotes"),
+                Arg::with_name("quoting-style")
+                    .long("quoting-style")
+                    .takes_value(true)
+                    .number_of_values(1)
+                    .multiple(true)
+                    .value_name("WORD")
+                    .possible_values(&QuotingStyle::NAMES)
+                    .help("Use quoting style WORD for names"),
+                Arg::with_name("hide-control-chars")
+                    .short("q")
+                    .long("hide-control-chars")
+                    .multiple(true)
+                    .help("Print ? instead of non-printable characters"),
+                Arg::with_name("show-control-chars")
+                    .long("show-control-chars")
+                    .multiple(true)
+                    .help("Print non-printable characters as-is"),
+                Arg::with_name("color")
+                    .long("color")
+                    .takes_value(true)
+                    .min_values(0)
+                    .require_equals(true)
+                    .value_name("WHEN")
+                    .possible_values(&["never", "auto", "always"])
+                    .help("Color names according to $LS_COLORS: never, auto or always"),
+                Arg::with_name("atime")
+                    .short("u")
+                    .multiple(true)
+                    .help("Show and sort by access time"),
+                Arg::with_name("ctime")
+                    .short("c")
+                    .multiple(true)
+                    .help("Show and sort by status change time"),
+                Arg::with_name("time")
+                    .long("time")
+                    .takes_value(true)
+                    .number_of_values(1)
+                    .multiple(true)
+                    .value_name("WORD")
+                    .possible_values(&[
+                        "atime", "access", "use", "ctime", "status", "mtime", "modification",
+                        "birth", "creation",
+                    ])
+                    .help("Show and sort by the time named by WORD instead of mtime"),
+                Arg::with_name("time-style")
+                    .long("time-style")
+                    .takes_value(true)
+                    .value_name("STYLE")
+                    .help("Time format: full-iso, long-iso, iso, locale or +FORMAT"),
+                Arg::with_name("json")
+                    .long("json")
+                    .help("Print one JSON object per entry"),
+                Arg::with_name("zero")
+                    .long("zero")
+                    .help("End each output line with NUL, not newline"),
+                Arg::with_name("dirnames")
+                    .short("d")
+                    .long("directory")
+                    .help("List directory names, not their contents"),
+                Arg::with_name("long")
+                    .short("l")
+                    .help("Use a long listing format"),
+                Arg::with_name("sort-time")
+                    .short("t")
+                    .multiple(true)
+                    .help("Sort by modification time, newest first"),
+                Arg::with_name("sort-size")
+                    .short("S")
+                    .multiple(true)
+                    .help("Sort by file size, largest first"),
+                Arg::with_name("sort-extension")
+                    .short("X")
+                    .multiple(true)
+                    .help("Sort alphabetically by extension"),
+                Arg::with_name("unsorted")
+                    .short("U")
+                    .multiple(true)
+                    .help("Do not sort; list entries in directory order"),
+                Arg::with_name("reverse")
+                    .short("r")
+                    .long("reverse")
+                    .help("Reverse the sort order"),
+                Arg::with_name("recursive")
+                    .short("R")
+                    .long("recursive")
+                    .help("List subdirectories recursively"),
+                Arg::with_name("one-per-line")
+                    .short("1")
+                    .multiple(true)
+                    .help("List one file per line"),
+                Arg::with_name("columns")
+                    .short("C")
+                    .multiple(true)
+                    .help("List entries in columns, sorted down each column"),
+                Arg::with_name("across")
+                    .short("x")
+                    .multiple(true)
+                    .help("List entries in columns, sorted across each row"),
+            ])
+    }
+
+    fn run(&self, ls_args: &ArgMatches) -> ExitCode {
+        let paths = ls_args
+            .values_of_os("files")
+            .map(applet::map_os_args_to_path_vec)
+            .unwrap_or_default();
+
+        let size_format = match ls_size_format(ls_args) {
+            Ok(format) => format,
+            Err(spec) => {
+                let message = format!("invalid --block-size argument '{}'", spec.to_string_lossy());
+                error::report("ls", None, message);
+                return ExitCode::InvalidUsage;
+            }
+        };
+
+        let time_style = match ls_args.value_of_os("time-style") {
+            Some(spec) => match spec.to_str().and_then(TimeStyle::parse) {
+                Some(style) => Some(style),
+                None => {
+                    let spec = spec.to_string_lossy();
+                    let message = format!("invalid --time-style argument '{}'", spec);
+                    error::report("ls", None, message);
+                    return ExitCode::InvalidUsage;
+                }
+            },
+            None => None,
+        };
+
+        let (all, almost_all) = ls_hidden(ls_args);
+        let ls_args = Args {
+            paths,
+            all,
+            shallow_dirs: ls_args.is_present("dirnames"),
+            long: ls_args.is_present("long"),
+            sort: ls_sort_key(ls_args),
+            time_field: ls_time_field(ls_args),
+            time_style,
+            reverse: ls_args.is_present("reverse"),
+            recursive: ls_args.is_present("recursive"),
+            layout: ls_layout(ls_args),
+            almost_all,
+            indicators: ls_indicator_style(ls_args),
+            size_format,
+            kibibytes: ls_args.is_present("kibibytes"),
+            show_blocks: ls_args.is_present("size"),
+            show_inode: ls_args.is_present("inode"),
+            numeric_ids: ls_args.is_present("numeric-uid-gid"),
+            dereference: ls_dereference(ls_args),
+            quoting: ls_quoting_style(ls_args),
+            color: match ls_args.value_of("color") {
+                Some("never") => ColorMode::Never,
+                Some("auto") => ColorMode::Auto,
+                // a bare --color means always
+                Some(_) | None if ls_args.is_present("color") => ColorMode::Always,
+                _ => ColorMode::Never,
+            },
+            json: ls_args.is_present("json"),
+            zero: ls_args.is_present("zero"),
+            hide_control: last_present(
+                ls_args,
+                &[("hide-control-chars", true), ("show-control-chars", false)],
+            ),
+        };
+        return main(ls_args);
+    }
+}
+
+/// When several sort options are given, the last one on the command line wins
+fn ls_sort_key(ls_args: &ArgMatches) -> Option<SortKey> {
+    let keys = [
+        ("sort-time", SortKey::Time),
+        ("sort-size", SortKey::Size),
+        ("sort-extension", SortKey::Extension),
+        ("unsorted", SortKey::Unsorted),
+    ];
+    last_present(ls_args, &keys)
+}
+
+fn ls_time_field(ls_args: &ArgMatches) -> TimeField {
+    let named_field = match ls_args.values_of("time").and_then(|mut v| v.next_back()) {
+        Some("atime") | Some("access") | Some("use") => TimeField::Access,
+        Some("ctime") | Some("status") => TimeField::Change,
+        Some("birth") | Some("creation") => TimeField::Birth,
+        _ => TimeField::Modification,
+    };
+
+    let fields = [
+        ("atime", TimeField::Access),
+        ("ctime", TimeField::Change),
+        ("time", named_field),
+    ];
+    last_present(ls_args, &fields).unwrap_or(TimeField::Modification)
+}
+
+fn ls_layout(ls_args: &ArgMatches) -> Option<Layout> {
+    let layouts = [
+        ("one-per-line", Layout::OneLine),
+        ("columns", Layout::Columns),
+        ("across", Layout::Across),
+    ];
+    last_present(ls_args, &layouts)
+}
+
+/// Whether -a or -A came last, as `(all, almost_all)`
+fn ls_hidden(ls_args: &ArgMatches) -> (bool, bool) {
+    let modes = [("all", (true, false)), ("almost-all", (false, true))];
+    last_present(ls_args, &modes).unwrap_or((false, false))
+}
+
+fn ls_indicator_style(ls_args: &ArgMatches) -> IndicatorStyle {
+    let styles = [
+        ("classify", IndicatorStyle::Classify),
+        ("slash-dirs", IndicatorStyle::Slash),
+    ];
+    last_present(ls_args, &styles).unwrap_or(IndicatorStyle::None)
+}
+
+fn ls_dereference(ls_args: &ArgMatches) -> Dereference {
+    let modes = [
+        ("dereference", Dereference::All),
+        ("dereference-command-line", Dereference::Operands),
+    ];
+    last_present(ls_args, &modes).unwrap_or(Dereference::DirectoryOperands)
+}
+
+fn ls_quoting_style(ls_args: &ArgMatches) -> QuotingStyle {
+    let named_style = ls_args
+        .values_of("quoting-style")
+        .and_then(|mut v| v.next_back())
+        .and_then(QuotingStyle::from_name);
+
+    let styles = [
+        ("escape", Some(QuotingStyle::Escape)),
+        ("literal", Some(QuotingStyle::Literal)),
+        ("quote-name", Some(QuotingStyle::C)),
+        ("quoting-style", named_style),
+    ];
+    last_present(ls_args, &styles)
+        .flatten()
+        .unwrap_or(QuotingStyle::Literal)
+}
+
+/// The size units picked by -h, --si and --block-size, or the unparseable --block-size value
+fn ls_size_format<'a>(ls_args: &'a ArgMatches) -> Result<Option<SizeFormat>, &'a OsStr> {
+    let block_size = match ls_args.values_of_os("block-size").and_then(|mut v| v.next_back()) {
+        Some(spec) => Some(spec.to_str().and_then(SizeFormat::parse).ok_or(spec)?),
+        None => None,
+    };
+
+    let formats = [
+        ("human-readable", Some(SizeFormat::Human { base: 1024 })),
+        ("si", Some(SizeFormat::Human { base: 1000 })),
+        ("block-size", block_size),
+    ];
+    Ok(last_present(ls_args, &formats).flatten())
+}
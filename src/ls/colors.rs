@@ -0,0 +1,150 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! Coloring of names based on the `LS_COLORS` environment variable, which is in the format written
+//! by `dircolors`: a ':' separated list of `key=SGR codes`, where each key is either a two letter
+//! file type like `di` or a glob like `*.tar`.
+
+use super::Entry;
+use nix::dir::Type;
+use std::collections::HashMap;
+use std::os::unix::ffi::OsStrExt;
+
+/// The colors used when `LS_COLORS` doesn't say otherwise. These match GNU's built in defaults.
+const DEFAULTS: [(&str, &str); 13] = [
+    ("di", "01;34"),
+    ("ln", "01;36"),
+    ("pi", "33"),
+    ("so", "01;35"),
+    ("do", "01;35"),
+    ("bd", "01;33"),
+    ("cd", "01;33"),
+    ("ex", "01;32"),
+    ("su", "37;41"),
+    ("sg", "30;43"),
+    ("st", "37;44"),
+    ("ow", "34;42"),
+    ("tw", "30;42"),
+];
+
+pub struct Colors {
+    /// Codes for file types, keyed on their two letter names
+    types: HashMap<String, String>,
+    /// Name suffixes and their codes, from keys like `*.tar`
+    suffixes: Vec<(Vec<u8>, String)>,
+}
+
+impl Colors {
+    /// Colors from the `LS_COLORS` environment variable, layered on top of the defaults
+    pub fn from_env() -> Colors {
+        let mut colors = Colors {
+            types: DEFAULTS
+                .iter()
+                .map(|&(key, code)| (key.to_string(), code.to_string()))
+                .collect(),
+            suffixes: Vec::new(),
+        };
+        if let Ok(spec) = std::env::var("LS_COLORS") {
+            colors.parse(&spec);
+        }
+        colors
+    }
+
+    fn parse(&mut self, spec: &str) {
+        for item in spec.split(':') {
+            let (key, code) = match item.find('=') {
+                Some(i) => (&item[..i], &item[i + 1..]),
+                // dircolors never writes these, so they're not worth complaining about
+                None => continue,
+            };
+
+            match key.strip_prefix('*') {
+                Some(suffix) => self.suffixes.push((suffix.as_bytes().to_vec(), code.to_string())),
+                None => {
+                    self.types.insert(key.to_string(), code.to_string());
+                }
+            }
+        }
+
+        // later entries take priority over earlier ones, so search them in reverse
+        self.suffixes.reverse();
+    }
+
+    /// The SGR codes to color the entry with, or `None` if it should be printed plainly
+    pub fn code_for(&self, entry: &Entry) -> Option<&str> {
+        let code = match entry.kind() {
+            Some(Type::Directory) => self.dir_code(entry),
+            Some(Type::Symlink) if entry.dangling => self.code("or").or_else(|| self.code("ln")),
+            Some(Type::Symlink) => self.code("ln"),
+            Some(Type::Fifo) => self.code("pi"),
+            Some(Type::Socket) => self.code("so"),
+            Some(Type::BlockDevice) => self.code("bd"),
+            Some(Type::CharacterDevice) => self.code("cd"),
+            Some(Type::File) => self.file_code(entry),
+            // something like a deleted file, which GNU calls "missing"
+            None => self.code("mi"),
+        };
+        code.or_else(|| self.code("no"))
+    }
+
+    /// The code for a key, ignoring ones that have been explicitly turned off
+    fn code(&self, key: &str) -> Option<&str> {
+        match self.types.get(key).map(String::as_str) {
+            None | Some("") | Some("0") | Some("00") => None,
+            Some(code) => Some(code),
+        }
+    }
+
+    fn dir_code(&self, entry: &Entry) -> Option<&str> {
+        let mode = entry.stat.as_ref().map(|s| s.st_mode).unwrap_or(0);
+        let sticky = mode & libc::S_ISVTX != 0;
+        let other_writable = mode & libc::S_IWOTH != 0;
+        match (sticky, other_writable) {
+            (true, true) => self.code("tw"),
+            (false, true) => self.code("ow"),
+            (true, false) => self.code("st"),
+            (false, false) => None,
+        }
+        .or_else(|| self.code("di"))
+    }
+
+    fn file_code(&self, entry: &Entry) -> Option<&str> {
+        let stat = entry.stat.as_ref();
+        let mode = stat.map(|s| s.st_mode).unwrap_or(0);
+        let exec_bits = libc::S_IXUSR | libc::S_IXGRP | libc::S_IXOTH;
+
+        let special = if mode & libc::S_ISUID != 0 && self.code("su").is_some() {
+            self.code("su")
+        } else if mode & libc::S_ISGID != 0 && self.code("sg").is_some() {
+            self.code("sg")
+        } else if mode & exec_bits != 0 && self.code("ex").is_some() {
+            self.code("ex")
+        } else if stat.map(|s| s.st_nlink > 1).unwrap_or(false) && self.code("mh").is_some() {
+            self.code("mh")
+        } else {
+            None
+        };
+
+        special
+            .or_else(|| self.suffix_code(entry.name.as_bytes()))
+            .or_else(|| self.code("fi"))
+    }
+
+    fn suffix_code(&self, name: &[u8]) -> Option<&str> {
+        // an exact match wins, but extensions are commonly written in either case, so fall back
+        // to ignoring it
+        let exact = self.suffixes.iter().find(|(suffix, _)| name.ends_with(suffix));
+        let found = exact.or_else(|| {
+            self.suffixes.iter().find(|(suffix, _)| {
+                name.len() >= suffix.len()
+                    && name[name.len() - suffix.len()..].eq_ignore_ascii_case(suffix)
+            })
+        });
+
+        match found.map(|(_, code)| code.as_str()) {
+            None | Some("") | Some("0") | Some("00") => None,
+            code => code,
+        }
+    }
+}
@@ -0,0 +1,114 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! `ls --json`, which writes one JSON object per entry so that scripts don't have to pick apart
+//! the long format. Every object is on its own line.
+//!
+//! JSON strings have to be unicode, but file names don't. A name that isn't valid UTF-8 is written
+//! with U+FFFD in place of the bad bytes, so it's still readable, and is followed by an extra
+//! `_bytes` field holding every byte of the name as an array of numbers, so the original can
+//! always be recovered. Valid names only get the string.
+
+use super::{stat_of, Entry};
+use crate::quote::{self, Unit};
+use nix::dir::Type;
+use std::fmt::Write;
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+
+/// Prints the entry as a JSON object, without a trailing newline. `dir` is the directory the entry
+/// was found in, if it didn't come from the command line.
+pub fn print_entry(entry: &Entry, dir: Option<&Path>) {
+    let stat = stat_of(entry);
+
+    let mut out = String::with_capacity(256);
+    out.push('{');
+    push_name(&mut out, "name", entry.name.as_bytes());
+
+    out.push(',');
+    match dir {
+        Some(dir) => push_name(&mut out, "path", dir.join(&entry.name).as_os_str().as_bytes()),
+        None => push_name(&mut out, "path", entry.name.as_bytes()),
+    }
+
+    let file_type = match entry.kind() {
+        Some(Type::File) => "file",
+        Some(Type::Directory) => "directory",
+        Some(Type::Symlink) => "symlink",
+        Some(Type::Fifo) => "fifo",
+        Some(Type::Socket) => "socket",
+        Some(Type::BlockDevice) => "block_device",
+        Some(Type::CharacterDevice) => "char_device",
+        None => "unknown",
+    };
+
+    // writing to a String can't fail
+    let _ = write!(
+        out,
+        ",\"type\":\"{}\",\"size\":{},\"mode\":{},\"nlink\":{},\"uid\":{},\"gid\":{},\"inode\":{}",
+        file_type,
+        stat.st_size,
+        stat.st_mode & 0o7777,
+        stat.st_nlink,
+        stat.st_uid,
+        stat.st_gid,
+        entry.inode(),
+    );
+
+    let times = [
+        ("atime", stat.st_atime, stat.st_atime_nsec),
+        ("mtime", stat.st_mtime, stat.st_mtime_nsec),
+        ("ctime", stat.st_ctime, stat.st_ctime_nsec),
+    ];
+    for &(field, sec, nsec) in times.iter() {
+        let _ = write!(out, ",\"{}\":{{\"sec\":{},\"nsec\":{}}}", field, sec, nsec);
+    }
+    // only looked up when asked for with --time=birth
+    if let Some((sec, nsec)) = entry.birth {
+        let _ = write!(out, ",\"btime\":{{\"sec\":{},\"nsec\":{}}}", sec, nsec);
+    }
+
+    out.push(',');
+    match entry.link_target.as_ref() {
+        Some(target) => push_name(&mut out, "target", target.as_bytes()),
+        None => out.push_str("\"target\":null"),
+    }
+    out.push('}');
+
+    print!("{}", out);
+}
+
+/// Writes `"field":"name"`, followed by `,"field_bytes":[...]` if the name isn't valid UTF-8
+fn push_name(out: &mut String, field: &str, bytes: &[u8]) {
+    let _ = write!(out, "\"{}\":", field);
+    push_string(out, bytes);
+
+    if std::str::from_utf8(bytes).is_err() {
+        let _ = write!(out, ",\"{}_bytes\":[", field);
+        for (i, b) in bytes.iter().enumerate() {
+            let separator = if i == 0 { "" } else { "," };
+            let _ = write!(out, "{}{}", separator, b);
+        }
+        out.push(']');
+    }
+}
+
+fn push_string(out: &mut String, bytes: &[u8]) {
+    out.push('"');
+    quote::for_each_unit(bytes, |unit| match unit {
+        Unit::Char('"') => out.push_str("\\\""),
+        Unit::Char('\\') => out.push_str("\\\\"),
+        Unit::Char('\n') => out.push_str("\\n"),
+        Unit::Char('\t') => out.push_str("\\t"),
+        Unit::Char(c) if c.is_control() => {
+            let mut buf = [0; 2];
+            for u in c.encode_utf16(&mut buf) {
+                let _ = write!(out, "\\u{:04x}", u);
+            }
+        }
+        Unit::Char(c) => out.push(c),
+        Unit::Byte(_) => out.push(char::REPLACEMENT_CHARACTER),
+    });
+    out.push('"');
+}
@@ -4,19 +4,32 @@
 
 #![allow(clippy::needless_return)]
 
+mod applet;
+mod install;
+// ls only formats dates and touch only parses them
+#[cfg(any(feature = "ls", feature = "touch"))]
+#[cfg_attr(not(all(feature = "ls", feature = "touch")), allow(dead_code))]
+mod datetime;
+mod error;
+#[cfg(feature = "ls")]
 mod ls;
+#[cfg(feature = "mkdir")]
 mod mkdir;
+#[cfg(feature = "mkdir")]
+mod mode;
+#[cfg(feature = "ls")]
+mod quote;
+#[cfg(feature = "rmdir")]
 mod rmdir;
+#[cfg(any(feature = "mkdir", feature = "touch"))]
+mod selinux;
+#[cfg(feature = "ls")]
+mod size;
+#[cfg(feature = "touch")]
 mod touch;
 
 use std::ffi::{OsStr, OsString};
 use std::path::Path;
-use std::fmt::Display;
-
-const EXIT_CODE_INVALID_USAGE: i32 = 1;
-const EXIT_CODE_NO_CWD: i32 = 2;
-const EXIT_CODE_READ_DIR: i32 = 3;
-const EXIT_CODE_UNKNOWN_ERR: i32 = 255;
 
 /// We put the actual main code inside another function so that we aren't calling exit() without
 /// calling destructors. This is just the required rust entrypoint.
@@ -31,13 +44,13 @@ fn main_code() -> Option<i32> {
     let mut args: Vec<_> = std::env::args_os().collect();
     if args.is_empty() {
         print_usage();
-        return Some(EXIT_CODE_INVALID_USAGE);
+        return Some(ExitCode::InvalidUsage as i32);
     } else {
         let cmd = match executable_name(&args[0]) {
             Some(cmd) => cmd,
             None => {
                 print_usage();
-                return Some(EXIT_CODE_INVALID_USAGE);
+                return Some(ExitCode::InvalidUsage as i32);
             }
         };
 
@@ -70,7 +83,7 @@ fn executable_name(first_cli_arg: &OsStr) -> Option<&OsStr> {
     let cmd = match cmd.file_name() {
         Some(c) => c,
         None => {
-            eprintln!("{}: not a command", cmd.display());
+            error::report("busycrate", Some(cmd), "not a command");
             return None;
         }
     };
@@ -79,173 +92,140 @@ fn executable_name(first_cli_arg: &OsStr) -> Option<&OsStr> {
 
 /// Main function with the "busycrate" argument split off from the rest
 fn run_with_args(busycrate: &OsStr, args: &[OsString]) -> Option<i32> {
-    use clap::{App, Arg, SubCommand};
-
-    fn map_os_args_to_path_vec<'a>(os_args: clap::OsValues<'a>) -> Vec<&'a Path> {
-        os_args.map(|os_str| os_str.as_ref()).collect()
-    };
+    use clap::{App, Arg, ArgGroup};
 
     let cmd = [busycrate.to_os_string()];
     let args = cmd.iter().chain(args);
     let app = App::new("BusyCrate")
         .version(clap::crate_version!())
-        .author(clap::crate_authors!("\n"))
+        .author(env!("CARGO_PKG_AUTHORS"))
         .about("Collection of Unix utilities")
         // these commands are often logged, written to files, etc.
         // color is usually unnecessary and potentially harmful
         .global_setting(clap::AppSettings::ColorNever)
-        .subcommand(
-            SubCommand::with_name("ls")
-                .about("List directory contents")
-                .args(&[
-                    Arg::with_name("files")
-                        .takes_value(true)
-                        .multiple(true),
-                    Arg::with_name("all")
-                        .short("a")
-                        .long("all")
-                        .help("List hidden files"),
-                    Arg::with_name("dirnames")
-                        .short("d")
-                        .long("directory")
-                        .help("List directory names, not their contents"),
-                ])
-        )
-        .subcommand(
-            SubCommand::with_name("touch")
-                .about("Create files and update their modified or access times")
-                .args(&[
-                    Arg::with_name("files")
-                        .takes_value(true)
-                        .multiple(true)
-                        .required(true),
-                    Arg::with_name("no-create")
-                        .short("c")
-                        .long("no-create")
-                        .help("Do not create any files"),
-                    Arg::with_name("atime-only")
-                        .short("a")
-                        .long("atime")
-                        .help("Only update atime"),
-                    Arg::with_name("mtime-only")
-                        .short("m")
-                        .long("mtime")
-                        .help("Only update mtime")
-                ])
-        )
-        .subcommand(
-            SubCommand::with_name("mkdir")
-                .about("Create directories")
-                .args(&[
-                    Arg::with_name("dirs")
-                        .takes_value(true)
-                        .multiple(true)
-                        .required(true),
-                    Arg::with_name("parents")
-                        .short("p")
-                        .long("parents")
-                        .help("Create parent directories if they don't exist")
-                ]),
-        )
-        .subcommand(
-            SubCommand::with_name("rmdir")
-                .about("Remove empty directories")
-                .arg(
-                    Arg::with_name("dirs")
-                        .takes_value(true)
-                        .multiple(true)
-                        .required(true),
-                ),
+        .args(&[
+            Arg::with_name("list")
+                .long("list")
+                .help("List the names every applet can be run with"),
+            Arg::with_name("install")
+                .long("install")
+                .requires("dir")
+                .conflicts_with("list")
+                .help("Link every applet's name to busycrate in DIR"),
+            Arg::with_name("uninstall")
+                .long("uninstall")
+                .requires("dir")
+                .conflicts_with("list")
+                .help("Remove links to busycrate made by --install from DIR"),
+            Arg::with_name("symbolic")
+                .short("s")
+                .requires("install")
+                .help("Install symlinks instead of hardlinks"),
+            Arg::with_name("force")
+                .long("force")
+                .requires("install")
+                .help("Replace existing files when installing"),
+            // only a directory for --install and --uninstall, so that anything else in its place
+            // is reported as an unknown command rather than taken as a directory
+            Arg::with_name("dir")
+                .value_name("DIR")
+                .takes_value(true)
+                .requires("links")
+                .hidden(true),
+        ])
+        .group(ArgGroup::with_name("links").args(&["install", "uninstall"]))
+        .subcommands(
+            applet::APPLETS
+                .iter()
+                .map(|applet| applet.command().aliases(applet.aliases())),
         );
 
     let matches = app.get_matches_from(args);
-    if let Some(ls_args) = matches.subcommand_matches("ls") {
-        let paths = ls_args
-            .values_of_os("files")
-            .map(map_os_args_to_path_vec)
-            .unwrap_or(Vec::new());
-
-        let ls_args = ls::Args {
-            paths,
-            all: ls_args.is_present("all"),
-            shallow_dirs: ls_args.is_present("dirnames"),
-        };
-        return Some(ls::main(ls_args));
-    } else if let Some(touch_args) = matches.subcommand_matches("touch") {
-        let paths = touch_args
-            .values_of_os("files")
-            .map(map_os_args_to_path_vec)
-            .unwrap_or(Vec::new());
-
-        let create = !touch_args.is_present("no-create");
-        let mtime_only = touch_args.is_present("mtime-only");
-        let atime_only = touch_args.is_present("atime-only");
-        let atime = atime_only || !mtime_only;
-        let mtime = mtime_only || !atime_only;
-
-        let touch_args = touch::Args {
-            paths,
-            create,
-            atime,
-            mtime,
-        };
-        return Some(touch::main(touch_args) as i32);
-    } else if let Some(mkdir_args) = matches.subcommand_matches("mkdir") {
-        let paths = mkdir_args
-            .values_of_os("dirs")
-            .map(map_os_args_to_path_vec)
-            .unwrap_or(Vec::new());
-
-        let mkdir_args = mkdir::Args {
-            create_parents: mkdir_args.is_present("parents"),
-            paths,
+    if matches.is_present("list") {
+        for applet in applet::APPLETS {
+            println!("{}", applet.name());
+            for alias in applet.aliases() {
+                println!("{}", alias);
+            }
+        }
+        return None;
+    }
+
+    if let Some(dir) = matches.value_of_os("dir") {
+        let dir = Path::new(dir);
+        let exit_code = if matches.is_present("install") {
+            let install_args = install::Args {
+                dir,
+                symbolic: matches.is_present("symbolic"),
+                force: matches.is_present("force"),
+            };
+            install::install(install_args)
+        } else {
+            install::uninstall(dir)
         };
-        return Some(mkdir::main(mkdir_args));
-    } else if let Some(rmdir_args) = matches.subcommand_matches("rmdir") {
-        let paths = rmdir_args
-            .values_of_os("dirs")
-            .map(map_os_args_to_path_vec)
-            .unwrap_or(Vec::new());
-
-        let rmdir_args = rmdir::Args { paths };
-        return Some(rmdir::main(rmdir_args));
-    } else {
-        print_usage();
-        return Some(1);
+        return Some(exit_code as i32);
     }
+
+    // clap reports the applet's real name even when it was run through an alias
+    if let (name, Some(applet_args)) = matches.subcommand() {
+        if let Some(applet) = applet::find(name) {
+            return Some(applet.run(applet_args) as i32);
+        }
+    }
+    print_usage();
+    return Some(ExitCode::InvalidUsage as i32);
 }
 
 fn print_usage() {
     println!(
         "Usage: busycrate [--help] <command> [options]
-                     <command> [options]"
+       busycrate --list
+       busycrate --install [-s] [--force] DIR
+       busycrate --uninstall DIR
+       <command> [options]"
     );
 }
 
-/// Common exit codes across all commands
+/// Common exit codes across all commands. They're ordered by severity, so when several things go
+/// wrong, the greatest one is what gets reported.
+// some codes go unused when the applets that report them aren't compiled in
+#[cfg_attr(not(all(feature = "ls", feature = "mkdir", feature = "rmdir", feature = "touch")), allow(dead_code))]
 #[repr(i32)]
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
 pub enum ExitCode {
     Success = 0,
     InvalidUsage = 1,
-    NoCwd = 2,
     ReadDir = 3,
     Stat = 4,
     Time = 5,
+    Exists = 6,
+    NotFound = 7,
+    NotDir = 8,
+    NotEmpty = 9,
+    Permission = 10,
     UnknownErr = 255,
 }
 
-pub struct FdPathDropper<P: Display>(i32, P);
+/// Closes a file descriptor when dropped, reporting any error on behalf of an applet
+#[cfg(any(feature = "mkdir", feature = "touch"))]
+pub struct FdPathDropper<'a> {
+    fd: i32,
+    applet: &'static str,
+    fpath: &'a Path,
+}
 
-impl<P: Display> FdPathDropper<P> {
-    pub fn new(fd: i32, fpath: P) -> Self {
-        Self(fd, fpath)
+#[cfg(any(feature = "mkdir", feature = "touch"))]
+impl<'a> FdPathDropper<'a> {
+    pub fn new(fd: i32, applet: &'static str, fpath: &'a Path) -> Self {
+        Self { fd, applet, fpath }
     }
 }
 
-impl<P: Display> Drop for FdPathDropper<P> {
+#[cfg(any(feature = "mkdir", feature = "touch"))]
+impl Drop for FdPathDropper<'_> {
     fn drop(&mut self) {
-        if let Err(e) = nix::unistd::close(self.0) {
-            eprintln!("Error closing file '{}': {}", self.1, e);
+        if let Err(e) = nix::unistd::close(self.fd) {
+            error::Error::new(error::Operation::Close, self.fpath, e).report(self.applet);
             // don't set the status code here since, really, the fd will be closed regardless of
             // any error. The stderr message is here just to let the user know that _something_
             // happened. If we were writing anything to the file, there would be potential for
@@ -2,72 +2,200 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
 
-use nix::sys::stat::Mode;
-use std::os::unix::ffi::OsStrExt;
-use std::path::Path;
+use nix::errno::Errno;
+use nix::fcntl::OFlag;
+use nix::sys::stat::{FchmodatFlags, Mode};
+use std::ffi::OsStr;
+use std::path::{Component, Path, PathBuf};
+use crate::applet::{self, Applet};
+use crate::error::{self, Error, Operation};
+use crate::{ExitCode, FdPathDropper};
+use clap::{App, Arg, ArgMatches, SubCommand};
 
 pub struct Args<'a> {
     pub create_parents: bool,
+    /// Exact permissions for the directories named on the command line, regardless of the umask.
+    /// Parents created by `create_parents` don't get these.
+    pub mode: Option<libc::mode_t>,
+    /// Print a message for each directory that gets created
+    pub verbose: bool,
+    /// SELinux security context to label every created directory with
+    pub context: Option<&'a OsStr>,
     pub paths: Vec<&'a Path>,
 }
 
-pub fn main(args: Args) -> i32 {
+pub struct Mkdir;
+
+impl Applet for Mkdir {
+    fn name(&self) -> &'static str {
+        "mkdir"
+    }
+
+    fn command(&self) -> App<'static, 'static> {
+        SubCommand::with_name("mkdir")
+            .about("Create directories")
+            .args(&[
+                Arg::with_name("dirs")
+                    .takes_value(true)
+                    .multiple(true)
+                    .required(true),
+                Arg::with_name("parents")
+                    .short("p")
+                    .long("parents")
+                    .help("Create parent directories if they don't exist"),
+                Arg::with_name("mode")
+                    .short("m")
+                    .long("mode")
+                    .takes_value(true)
+                    .value_name("MODE")
+                    .help("Set the permissions of created directories, like 0750 or u=rwx,g=rx,o="),
+                Arg::with_name("verbose")
+                    .short("v")
+                    .long("verbose")
+                    .help("Print a message for each created directory"),
+                Arg::with_name("context")
+                    .short("Z")
+                    .long("context")
+                    .takes_value(true)
+                    .value_name("CTX")
+                    .help("Set the SELinux security context of created directories to CTX"),
+            ])
+    }
+
+    fn run(&self, mkdir_args: &ArgMatches) -> ExitCode {
+        if mkdir_args.is_present("context") && !crate::selinux::ENABLED {
+            return crate::selinux::unsupported("mkdir");
+        }
+
+        let paths = mkdir_args
+            .values_of_os("dirs")
+            .map(applet::map_os_args_to_path_vec)
+            .unwrap_or_default();
+
+        let mode = match mkdir_args.value_of_os("mode") {
+            Some(spec) => match spec.to_str().and_then(crate::mode::ModeSpec::parse) {
+                // symbolic modes start from read+write+execute for everyone
+                Some(mode) => Some(mode.apply(0o777, crate::mode::current_umask(), true)),
+                None => {
+                    let spec = spec.to_string_lossy();
+                    error::report("mkdir", None, format_args!("invalid mode '{}'", spec));
+                    return ExitCode::InvalidUsage;
+                }
+            },
+            None => None,
+        };
+
+        let mkdir_args = Args {
+            create_parents: mkdir_args.is_present("parents"),
+            mode,
+            verbose: mkdir_args.is_present("verbose"),
+            context: mkdir_args.value_of_os("context"),
+            paths,
+        };
+        return main(mkdir_args);
+    }
+}
+
+pub fn main(args: Args) -> ExitCode {
     if args.paths.is_empty() {
-        eprintln!("missing file operand");
+        error::report("mkdir", None, "missing file operand");
         eprintln!("Try 'mkdir --help' for more information");
-        return crate::EXIT_CODE_INVALID_USAGE;
+        return ExitCode::InvalidUsage;
     }
 
-    let mut status = 0;
+    let mut status = ExitCode::Success;
     'ARG_LOOP: for total_path in args.paths {
         let mode = Mode::S_IRUSR | Mode::S_IWUSR | Mode::S_IXUSR; // user can read+write+execute
         let mode = mode | Mode::S_IRGRP | Mode::S_IWGRP | Mode::S_IXGRP; // group can read+write+execute
         let mode = mode | Mode::S_IROTH | Mode::S_IXOTH; // others can read+execute
 
-        let no_parents = [total_path.as_os_str()];
-        let mut with_parents; // collection of ['first', 'first/second', 'first/second/third', ...]
         let creations = if args.create_parents {
-            // split total_path into slices over all prefix directories and collect them into a Vec
-            with_parents = Vec::new();
-            let raw = total_path.as_os_str().as_bytes();
-            let mut search_from = 0;
-
-            while let Some(i) = raw
-                .get(search_from..) // start search from this index, checked slice
-                .unwrap_or(&[]) // trick so position() returns None when we've reached the end
-                .iter()
-                .position(|&b| b == b'/')
-            {
-                let prefix_end = search_from + i;
-                search_from = prefix_end + 1;
-                with_parents.push(OsStrExt::from_bytes(&raw[0..prefix_end]));
-            }
-
-            // the path may not end with '/', so we have to add the final component
-            if search_from < raw.len() {
-                with_parents.push(OsStrExt::from_bytes(raw));
+            // every prefix of the path, e.g. ['first', 'first/second', 'first/second/third'].
+            // Going by components skips over repeated slashes and '.', which would otherwise
+            // show up as prefixes that can't be created.
+            let mut prefix = PathBuf::new();
+            let mut with_parents = Vec::new();
+            for component in total_path.components() {
+                prefix.push(component);
+                match component {
+                    // these always exist, so there's nothing to create
+                    Component::Prefix(_) | Component::RootDir | Component::CurDir => {}
+                    Component::ParentDir | Component::Normal(_) => with_parents.push(prefix.clone()),
+                }
             }
-
-            with_parents.as_slice()
+            with_parents
         } else {
-            &no_parents[..]
+            vec![total_path.to_path_buf()]
         };
 
-        for &fpath in creations {
-            match nix::unistd::mkdir(fpath, mode) {
-                Ok(()) => {}
-                Err(nix::Error::Sys(nix::errno::Errno::EEXIST)) if args.create_parents => {
-                    // ignore this error
+        for (i, fpath) in creations.iter().enumerate() {
+            let is_operand = i + 1 == creations.len();
+            // the operand is created with its explicit mode straight away, so that it's never
+            // more accessible than was asked for
+            let create_mode = match args.mode {
+                Some(explicit_mode) if is_operand => Mode::from_bits_truncate(explicit_mode),
+                _ => mode,
+            };
+            let result = nix::unistd::mkdir(fpath, create_mode);
+            if result.is_ok() && args.verbose {
+                println!("created directory '{}'", fpath.display());
+            }
+            if let (Ok(()), Some(context)) = (result, args.context) {
+                if let Err(e) = set_context(fpath, context) {
+                    let e = Error::new(Operation::SetContext, fpath, e);
+                    status = status.max(e.report("mkdir"));
+                }
+            }
+
+            match result {
+                Ok(()) if is_operand => {
+                    // mkdir's mode is filtered through the umask and can't set the special bits,
+                    // so set it again afterwards if that kept it from being what was asked for
+                    let umask = crate::mode::current_umask();
+                    let needs_chmod = |m: &libc::mode_t| m & umask & 0o777 != 0 || m & 0o7000 != 0;
+                    if let Some(explicit_mode) = args.mode.filter(needs_chmod) {
+                        let explicit_mode = Mode::from_bits_truncate(explicit_mode);
+                        let result = nix::sys::stat::fchmodat(
+                            None,
+                            fpath,
+                            explicit_mode,
+                            FchmodatFlags::FollowSymlink,
+                        );
+                        if let Err(e) = result {
+                            let e = Error::new(Operation::SetPermissions, fpath, e);
+                            status = status.max(e.report("mkdir"));
+                        }
+                    }
+                }
+                Ok(()) => {
+                    // POSIX has parents keep the default mode, but with write and search
+                    // permission for the owner even if the umask says otherwise, since otherwise
+                    // the next directory couldn't be created inside it
+                    let umask = crate::mode::current_umask();
+                    if umask & 0o300 != 0 {
+                        let parent_mode = Mode::from_bits_truncate((mode.bits() & !umask) | 0o300);
+                        let _ = nix::sys::stat::fchmodat(
+                            None,
+                            fpath,
+                            parent_mode,
+                            FchmodatFlags::FollowSymlink,
+                        );
+                    }
+                }
+                Err(nix::Error::Sys(Errno::EEXIST)) if args.create_parents => {
                     // allow directories to exist already if we're creating each component in the
-                    // path
+                    // path, but anything else in the way means the rest can't be created. The
+                    // operand itself simply already exists.
+                    if !fpath.is_dir() {
+                        let errno = if is_operand { Errno::EEXIST } else { Errno::ENOTDIR };
+                        let e = Error::new(Operation::CreateDir, fpath, nix::Error::Sys(errno));
+                        status = status.max(e.report("mkdir"));
+                        continue 'ARG_LOOP;
+                    }
                 }
                 Err(e) => {
-                    eprintln!(
-                        "Unable to create directory '{}': {}",
-                        Path::new(fpath).display(),
-                        e
-                    );
-                    status = crate::EXIT_CODE_UNKNOWN_ERR;
+                    let e = Error::new(Operation::CreateDir, fpath, e);
+                    status = status.max(e.report("mkdir"));
                     continue 'ARG_LOOP;
                 }
             }
@@ -76,3 +204,12 @@ pub fn main(args: Args) -> i32 {
 
     status
 }
+
+/// Labels a directory that was just created. The directory is opened so that the label is set
+/// on the same inode even if something replaces the path in the meantime.
+fn set_context(dpath: &Path, context: &OsStr) -> nix::Result<()> {
+    let flags = OFlag::O_RDONLY | OFlag::O_DIRECTORY | OFlag::O_NOFOLLOW;
+    let fd = nix::fcntl::open(dpath, flags, Mode::empty())?;
+    let _dropper = FdPathDropper::new(fd, "mkdir", dpath);
+    crate::selinux::set_context(fd, context)
+}
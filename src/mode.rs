@@ -0,0 +1,227 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! Parsing of file modes in the form `chmod` accepts, shared by every command that takes a `-m`
+//! option. A mode is either an octal number like `0755`, or a comma separated list of symbolic
+//! clauses like `u=rwx,g+rx,o-w` that are applied on top of an existing mode.
+
+use libc::mode_t;
+
+/// All of the permission bits, along with setuid, setgid and sticky
+const ALL_BITS: mode_t = 0o7777;
+
+const OPERATORS: [char; 3] = ['+', '-', '='];
+
+/// A parsed mode, ready to be applied to a file
+pub enum ModeSpec {
+    /// An absolute mode, which replaces the existing one entirely
+    Octal(mode_t),
+    Symbolic(Vec<Clause>),
+}
+
+/// One comma separated part of a symbolic mode, like `ug+rw`
+pub struct Clause {
+    /// The bits that `u`, `g`, `o` and `a` select. Zero means none were given, which acts like
+    /// `a` except that bits set in the umask are left alone.
+    who: mode_t,
+    actions: Vec<Action>,
+}
+
+struct Action {
+    op: Op,
+    perms: Perms,
+}
+
+#[derive(Clone, Copy)]
+enum Op {
+    Add,
+    Remove,
+    Set,
+}
+
+enum Perms {
+    /// Letters from `rwxXst`, with `X` kept separate since it depends on the file
+    Letters { bits: mode_t, conditional_x: bool },
+    /// A copy of the permissions one of `u`, `g` or `o` already has
+    Copy(mode_t),
+}
+
+impl ModeSpec {
+    /// Parses an octal or symbolic mode, returning `None` if it isn't valid
+    pub fn parse(spec: &str) -> Option<ModeSpec> {
+        if !spec.is_empty() && spec.bytes().all(|b| (b'0'..=b'7').contains(&b)) {
+            let mode = mode_t::from_str_radix(spec, 8).ok()?;
+            return if mode <= ALL_BITS { Some(ModeSpec::Octal(mode)) } else { None };
+        }
+
+        spec.split(',')
+            .map(parse_clause)
+            .collect::<Option<Vec<_>>>()
+            .map(ModeSpec::Symbolic)
+    }
+
+    /// The mode that results from applying this to `mode`. `umask` limits clauses that don't say
+    /// who they apply to, and `is_dir` decides whether `X` grants execute permission.
+    pub fn apply(&self, mode: mode_t, umask: mode_t, is_dir: bool) -> mode_t {
+        let clauses = match self {
+            ModeSpec::Octal(octal) => return *octal,
+            ModeSpec::Symbolic(clauses) => clauses,
+        };
+
+        let mut mode = mode & ALL_BITS;
+        for clause in clauses {
+            let (affected, cleared) = match clause.who {
+                0 => (ALL_BITS & !umask, ALL_BITS),
+                who => (who, who),
+            };
+
+            for action in clause.actions.iter() {
+                let bits = match action.perms {
+                    Perms::Letters { bits, conditional_x } => {
+                        // X only grants execute to directories and files someone can already
+                        // execute
+                        let executable = is_dir || mode & 0o111 != 0;
+                        if conditional_x && executable { bits | 0o111 } else { bits }
+                    }
+                    Perms::Copy(source) => {
+                        let perms = (mode & source) >> source.trailing_zeros();
+                        perms * 0o111
+                    }
+                } & affected;
+
+                mode = match action.op {
+                    Op::Add => mode | bits,
+                    Op::Remove => mode & !bits,
+                    Op::Set => (mode & !cleared) | bits,
+                };
+            }
+        }
+        mode
+    }
+}
+
+fn parse_clause(clause: &str) -> Option<Clause> {
+    let op_start = clause.find(OPERATORS)?;
+    let (who_letters, mut rest) = clause.split_at(op_start);
+
+    let mut who = 0;
+    for c in who_letters.chars() {
+        who |= match c {
+            'u' => 0o4700,
+            'g' => 0o2070,
+            'o' => 0o1007,
+            'a' => ALL_BITS,
+            _ => return None,
+        };
+    }
+
+    let mut actions = Vec::new();
+    while let Some(c) = rest.chars().next() {
+        let op = match c {
+            '+' => Op::Add,
+            '-' => Op::Remove,
+            '=' => Op::Set,
+            _ => return None,
+        };
+        rest = &rest[1..];
+
+        let perms_end = rest.find(OPERATORS).unwrap_or(rest.len());
+        let (perms, next) = rest.split_at(perms_end);
+        rest = next;
+        actions.push(Action { op, perms: parse_perms(perms)? });
+    }
+
+    Some(Clause { who, actions })
+}
+
+fn parse_perms(perms: &str) -> Option<Perms> {
+    match perms {
+        "u" => return Some(Perms::Copy(0o700)),
+        "g" => return Some(Perms::Copy(0o070)),
+        "o" => return Some(Perms::Copy(0o007)),
+        _ => {}
+    }
+
+    let mut bits = 0;
+    let mut conditional_x = false;
+    for c in perms.chars() {
+        match c {
+            'r' => bits |= 0o444,
+            'w' => bits |= 0o222,
+            'x' => bits |= 0o111,
+            'X' => conditional_x = true,
+            's' => bits |= libc::S_ISUID | libc::S_ISGID,
+            't' => bits |= libc::S_ISVTX,
+            _ => return None,
+        }
+    }
+    Some(Perms::Letters { bits, conditional_x })
+}
+
+/// The process's file mode creation mask. There's no way to read it without setting it, so it's
+/// put straight back.
+pub fn current_umask() -> mode_t {
+    let mask = unsafe { libc::umask(0) };
+    unsafe { libc::umask(mask) };
+    mask
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn apply(spec: &str, mode: mode_t, umask: mode_t, is_dir: bool) -> mode_t {
+        ModeSpec::parse(spec).unwrap().apply(mode, umask, is_dir)
+    }
+
+    #[test]
+    fn octal() {
+        assert_eq!(apply("755", 0o600, 0o022, false), 0o755);
+        assert_eq!(apply("0700", 0o777, 0o000, true), 0o700);
+        assert_eq!(apply("7777", 0, 0o777, false), 0o7777);
+        assert!(ModeSpec::parse("10000").is_none());
+    }
+
+    #[test]
+    fn symbolic() {
+        assert_eq!(apply("u=rwx,g=rx,o=", 0o777, 0o022, true), 0o750);
+        assert_eq!(apply("u+x", 0o644, 0o022, false), 0o744);
+        assert_eq!(apply("go-r", 0o644, 0o022, false), 0o600);
+        assert_eq!(apply("a=r", 0o777, 0o022, false), 0o444);
+        // several actions in one clause apply in order
+        assert_eq!(apply("u+r-w", 0o200, 0o022, false), 0o400);
+        assert_eq!(apply("u+s,g+s,o+t", 0o755, 0o022, true), 0o7755);
+    }
+
+    #[test]
+    fn conditional_execute() {
+        assert_eq!(apply("a+X", 0o644, 0o022, false), 0o644);
+        assert_eq!(apply("a+X", 0o744, 0o022, false), 0o755);
+        assert_eq!(apply("a+X", 0o644, 0o022, true), 0o755);
+    }
+
+    #[test]
+    fn copies() {
+        assert_eq!(apply("g=u", 0o740, 0o022, false), 0o770);
+        assert_eq!(apply("o+g", 0o750, 0o022, false), 0o755);
+        assert_eq!(apply("u-o", 0o707, 0o022, false), 0o007);
+    }
+
+    #[test]
+    fn umask_limits_clauses_without_who() {
+        assert_eq!(apply("+x", 0o644, 0o022, false), 0o755);
+        assert_eq!(apply("+w", 0o444, 0o022, false), 0o644);
+        // bits the umask masks are left alone, rather than removed
+        assert_eq!(apply("-w", 0o666, 0o022, false), 0o466);
+        assert_eq!(apply("=r", 0o777, 0o022, false), 0o444);
+        assert_eq!(apply("=rw", 0o000, 0o077, false), 0o600);
+    }
+
+    #[test]
+    fn rejected() {
+        for spec in ["", "u", "8", "u+q", "u+rw,", "x=r", "0o755", "-9"].iter() {
+            assert!(ModeSpec::parse(spec).is_none(), "{}", spec);
+        }
+    }
+}
@@ -0,0 +1,301 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! Quoting of file names for output. Unix file names can contain any byte other than '/' and NUL,
+//! including newlines, terminal escape sequences and invalid UTF-8, so printing them as-is isn't
+//! always safe for whatever is reading the output.
+
+use std::borrow::Cow;
+
+/// How file names are written out, following the names used by GNU's `--quoting-style`
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum QuotingStyle {
+    /// The name as-is
+    Literal,
+    /// Surrounded in single quotes when the shell would otherwise interpret part of it
+    Shell,
+    /// Like `Shell`, but with non-printable characters written as `$'\n'` sections
+    ShellEscape,
+    /// Non-printable characters and spaces written as C escapes, without surrounding quotes
+    Escape,
+    /// Surrounded in double quotes, with non-printable characters written as C escapes
+    C,
+}
+
+impl QuotingStyle {
+    /// Looks up a style by the name used on the command line
+    pub fn from_name(name: &str) -> Option<QuotingStyle> {
+        match name {
+            "literal" => Some(QuotingStyle::Literal),
+            "shell" => Some(QuotingStyle::Shell),
+            "shell-escape" => Some(QuotingStyle::ShellEscape),
+            "escape" => Some(QuotingStyle::Escape),
+            "c" => Some(QuotingStyle::C),
+            _ => None,
+        }
+    }
+
+    pub const NAMES: [&'static str; 5] = ["literal", "shell", "shell-escape", "escape", "c"];
+}
+
+/// A piece of a file name: either a whole character, or a byte that isn't part of valid UTF-8
+#[derive(Clone, Copy)]
+pub enum Unit {
+    Char(char),
+    Byte(u8),
+}
+
+impl Unit {
+    pub fn is_printable(self) -> bool {
+        match self {
+            Unit::Char(c) => !c.is_control(),
+            Unit::Byte(_) => false,
+        }
+    }
+}
+
+/// Splits a name into characters, keeping hold of the bytes that aren't valid UTF-8
+pub fn for_each_unit(mut bytes: &[u8], mut f: impl FnMut(Unit)) {
+    while !bytes.is_empty() {
+        let (valid, invalid_len) = match std::str::from_utf8(bytes) {
+            Ok(s) => (s, 0),
+            Err(e) => {
+                let valid = std::str::from_utf8(&bytes[..e.valid_up_to()]).unwrap_or_default();
+                let rest = bytes.len() - e.valid_up_to();
+                (valid, e.error_len().unwrap_or(rest))
+            }
+        };
+        valid.chars().for_each(|c| f(Unit::Char(c)));
+
+        let rest = &bytes[valid.len()..];
+        rest[..invalid_len].iter().for_each(|&b| f(Unit::Byte(b)));
+        bytes = &rest[invalid_len..];
+    }
+}
+
+/// Quotes `name` according to `style`. When `hide_control` is set, styles that don't escape
+/// non-printable characters replace them with '?' instead.
+pub fn quote(name: &[u8], style: QuotingStyle, hide_control: bool) -> Cow<'_, [u8]> {
+    match style {
+        QuotingStyle::Literal => {
+            if !hide_control || is_all_printable(name) {
+                return Cow::Borrowed(name);
+            }
+            let mut quoted = Vec::with_capacity(name.len());
+            for_each_unit(name, |unit| push_literal(&mut quoted, unit, hide_control));
+            Cow::Owned(quoted)
+        }
+        QuotingStyle::Shell | QuotingStyle::ShellEscape => {
+            if is_shell_safe(name) {
+                return Cow::Borrowed(name);
+            }
+            let escape = style == QuotingStyle::ShellEscape;
+            Cow::Owned(shell_quote(name, escape, hide_control))
+        }
+        QuotingStyle::Escape => {
+            let mut quoted = Vec::with_capacity(name.len());
+            for_each_unit(name, |unit| match unit {
+                Unit::Char(' ') => quoted.extend_from_slice(b"\\ "),
+                unit => push_escaped(&mut quoted, unit),
+            });
+            Cow::Owned(quoted)
+        }
+        QuotingStyle::C => {
+            let mut quoted = Vec::with_capacity(name.len() + 2);
+            quoted.push(b'"');
+            for_each_unit(name, |unit| match unit {
+                Unit::Char('"') => quoted.extend_from_slice(b"\\\""),
+                unit => push_escaped(&mut quoted, unit),
+            });
+            quoted.push(b'"');
+            Cow::Owned(quoted)
+        }
+    }
+}
+
+fn is_all_printable(name: &[u8]) -> bool {
+    let mut printable = true;
+    for_each_unit(name, |unit| printable &= unit.is_printable());
+    printable
+}
+
+/// Whether or not a name can be pasted into a shell without any quoting
+fn is_shell_safe(name: &[u8]) -> bool {
+    let mut safe = !name.is_empty();
+    for_each_unit(name, |unit| {
+        safe &= match unit {
+            Unit::Char(c) if c.is_ascii() => c.is_ascii_alphanumeric() || "%+,-./:=@_^".contains(c),
+            // the shell doesn't treat anything outside of ascii specially
+            unit => unit.is_printable(),
+        }
+    });
+    safe
+}
+
+fn shell_quote(name: &[u8], escape: bool, hide_control: bool) -> Vec<u8> {
+    let mut quoted = Vec::with_capacity(name.len() + 2);
+    let mut in_quotes = false;
+
+    for_each_unit(name, |unit| {
+        if escape && !unit.is_printable() {
+            // the only way to write these in a shell without including them raw is ANSI-C quoting,
+            // which has to happen outside of the single quotes
+            if in_quotes {
+                quoted.push(b'\'');
+                in_quotes = false;
+            }
+            quoted.extend_from_slice(b"$'");
+            push_escaped(&mut quoted, unit);
+            quoted.push(b'\'');
+            return;
+        }
+
+        if !in_quotes {
+            quoted.push(b'\'');
+            in_quotes = true;
+        }
+        match unit {
+            // a single quote can't appear inside single quotes, so close them, add an escaped
+            // quote, and open them again
+            Unit::Char('\'') => quoted.extend_from_slice(b"'\\''"),
+            unit => push_literal(&mut quoted, unit, hide_control),
+        }
+    });
+
+    if in_quotes {
+        quoted.push(b'\'');
+    }
+    quoted
+}
+
+fn push_literal(quoted: &mut Vec<u8>, unit: Unit, hide_control: bool) {
+    match unit {
+        _ if hide_control && !unit.is_printable() => quoted.push(b'?'),
+        Unit::Char(c) => push_char(quoted, c),
+        Unit::Byte(b) => quoted.push(b),
+    }
+}
+
+/// Writes the unit out as-is if it's printable, and as a C escape sequence otherwise
+fn push_escaped(quoted: &mut Vec<u8>, unit: Unit) {
+    let c = match unit {
+        Unit::Char(c) => c,
+        Unit::Byte(b) => return push_octal(quoted, b),
+    };
+
+    let escaped: &[u8] = match c {
+        '\\' => b"\\\\",
+        '\x07' => b"\\a",
+        '\x08' => b"\\b",
+        '\t' => b"\\t",
+        '\n' => b"\\n",
+        '\x0b' => b"\\v",
+        '\x0c' => b"\\f",
+        '\r' => b"\\r",
+        c if !c.is_control() => return push_char(quoted, c),
+        c => {
+            let mut buf = [0; 4];
+            c.encode_utf8(&mut buf).bytes().for_each(|b| push_octal(quoted, b));
+            return;
+        }
+    };
+    quoted.extend_from_slice(escaped);
+}
+
+fn push_octal(quoted: &mut Vec<u8>, b: u8) {
+    quoted.extend_from_slice(&[b'\\', b'0' + (b >> 6), b'0' + ((b >> 3) & 7), b'0' + (b & 7)]);
+}
+
+fn push_char(quoted: &mut Vec<u8>, c: char) {
+    let mut buf = [0; 4];
+    quoted.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn q(name: &[u8], style: QuotingStyle) -> Vec<u8> {
+        quote(name, style, false).into_owned()
+    }
+
+    fn hidden(name: &[u8], style: QuotingStyle) -> Vec<u8> {
+        quote(name, style, true).into_owned()
+    }
+
+    #[test]
+    fn units() {
+        let mut units = Vec::new();
+        for_each_unit("a\u{e9}\n".as_bytes(), |unit| units.push(unit));
+        for_each_unit(b"\xffb\xe2\x82", |unit| units.push(unit));
+        let described: Vec<_> = units
+            .into_iter()
+            .map(|unit| match unit {
+                Unit::Char(c) => format!("{:?}", c),
+                Unit::Byte(b) => format!("{:#x}", b),
+            })
+            .collect();
+        // a truncated sequence at the end is still split into its bytes
+        let expected = ["'a'", "'\u{e9}'", "'\\n'", "0xff", "'b'", "0xe2", "0x82"];
+        assert_eq!(described, expected);
+    }
+
+    #[test]
+    fn literal() {
+        use QuotingStyle::Literal;
+        assert_eq!(q(b"a b\n\xff", Literal), b"a b\n\xff");
+        assert_eq!(hidden(b"a b\n\xff", Literal), b"a b??");
+        assert_eq!(hidden("\u{e9}\u{85}".as_bytes(), Literal), "\u{e9}?".as_bytes());
+        assert!(matches!(quote(b"plain", Literal, true), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn shell() {
+        use QuotingStyle::Shell;
+        assert_eq!(q(b"plain-name_1.txt", Shell), b"plain-name_1.txt");
+        assert_eq!(q("caf\u{e9}".as_bytes(), Shell), "caf\u{e9}".as_bytes());
+        assert_eq!(q(b"a b", Shell), b"'a b'");
+        assert_eq!(q(b"$HOME", Shell), b"'$HOME'");
+        assert_eq!(q(b"it's", Shell), b"'it'\\''s'");
+        assert_eq!(q(b"'", Shell), b"''\\'''");
+        // without escapes, control characters and invalid bytes are only quoted
+        assert_eq!(q(b"a\nb\xff", Shell), b"'a\nb\xff'");
+        assert_eq!(hidden(b"a\nb\xff", Shell), b"'a?b?'");
+    }
+
+    #[test]
+    fn shell_escape() {
+        use QuotingStyle::ShellEscape;
+        assert_eq!(q(b"plain", ShellEscape), b"plain");
+        assert_eq!(q(b"a\nb", ShellEscape), b"'a'$'\\n''b'");
+        assert_eq!(q(b"\n", ShellEscape), b"$'\\n'");
+        assert_eq!(q(b"\t\x1b", ShellEscape), b"$'\\t'$'\\033'");
+        assert_eq!(q(b"a\xffb", ShellEscape), b"'a'$'\\377''b'");
+        assert_eq!(q(b"it's\n", ShellEscape), b"'it'\\''s'$'\\n'");
+        // escaping takes priority over hiding
+        assert_eq!(hidden(b"a\n", ShellEscape), b"'a'$'\\n'");
+    }
+
+    #[test]
+    fn escape() {
+        use QuotingStyle::Escape;
+        assert_eq!(q(b"plain", Escape), b"plain");
+        assert_eq!(q(b"a b\n", Escape), b"a\\ b\\n");
+        assert_eq!(q(b"\x01\x7f\xff", Escape), b"\\001\\177\\377");
+        assert_eq!(q(b"back\\slash", Escape), b"back\\\\slash");
+        assert_eq!(q(b"it's \"x\"", Escape), b"it's\\ \"x\"");
+        assert_eq!(q("\u{85}".as_bytes(), Escape), b"\\302\\205");
+        assert_eq!(hidden(b"\r", Escape), b"\\r");
+    }
+
+    #[test]
+    fn c() {
+        use QuotingStyle::C;
+        assert_eq!(q(b"plain", C), b"\"plain\"");
+        assert_eq!(q(b"say \"hi\"\n", C), b"\"say \\\"hi\\\"\\n\"");
+        assert_eq!(q(b"it's", C), b"\"it's\"");
+        assert_eq!(q(b"\x07\x08\x0b\x0c\xff", C), b"\"\\a\\b\\v\\f\\377\"");
+        assert_eq!(q("\u{e9}".as_bytes(), C), "\"\u{e9}\"".as_bytes());
+    }
+}
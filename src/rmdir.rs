@@ -2,27 +2,104 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
 
+use nix::errno::Errno;
+use nix::unistd::UnlinkatFlags;
 use std::path::Path;
+use crate::applet::{self, Applet};
+use crate::error::{self, Error, Operation};
+use crate::ExitCode;
+use clap::{App, Arg, ArgMatches, SubCommand};
 
 pub struct Args<'a> {
     pub paths: Vec<&'a Path>,
+    /// Also remove each parent directory named in the path once it's empty
+    pub remove_parents: bool,
+    /// Don't treat directories that still have something in them as an error
+    pub ignore_non_empty: bool,
+    /// Print a message for each directory before it's removed
+    pub verbose: bool,
 }
 
-pub fn main(args: Args) -> i32 {
+pub struct Rmdir;
+
+impl Applet for Rmdir {
+    fn name(&self) -> &'static str {
+        "rmdir"
+    }
+
+    fn command(&self) -> App<'static, 'static> {
+        SubCommand::with_name("rmdir")
+            .about("Remove empty directories")
+            .args(&[
+                Arg::with_name("dirs")
+                    .takes_value(true)
+                    .multiple(true)
+                    .required(true),
+                Arg::with_name("parents")
+                    .short("p")
+                    .long("parents")
+                    .help("Remove each parent directory in the path as well"),
+                Arg::with_name("ignore-fail-on-non-empty")
+                    .long("ignore-fail-on-non-empty")
+                    .help("Ignore directories that aren't empty"),
+                Arg::with_name("verbose")
+                    .short("v")
+                    .long("verbose")
+                    .help("Print a message for each directory removed"),
+            ])
+    }
+
+    fn run(&self, rmdir_args: &ArgMatches) -> ExitCode {
+        let paths = rmdir_args
+            .values_of_os("dirs")
+            .map(applet::map_os_args_to_path_vec)
+            .unwrap_or_default();
+
+        let rmdir_args = Args {
+            paths,
+            remove_parents: rmdir_args.is_present("parents"),
+            ignore_non_empty: rmdir_args.is_present("ignore-fail-on-non-empty"),
+            verbose: rmdir_args.is_present("verbose"),
+        };
+        return main(rmdir_args);
+    }
+}
+
+pub fn main(args: Args) -> ExitCode {
     if args.paths.is_empty() {
-        eprintln!("missing file operand");
+        error::report("rmdir", None, "missing file operand");
         eprintln!("Try 'rmdir --help' for more information");
-        return crate::EXIT_CODE_INVALID_USAGE;
+        return ExitCode::InvalidUsage;
     }
 
-    let mut status = 0;
+    let mut status = ExitCode::Success;
     for fpath in args.paths {
-        match std::fs::remove_dir(fpath) {
-            Ok(()) => {}
-            Err(e) => {
-                eprintln!("Unable to remove '{}': {}", fpath.display(), e);
-                status = crate::EXIT_CODE_UNKNOWN_ERR;
+        let mut next = Some(fpath);
+        while let Some(dpath) = next {
+            if args.verbose {
+                println!("removing directory '{}'", dpath.display());
             }
+
+            match nix::unistd::unlinkat(None, dpath, UnlinkatFlags::RemoveDir) {
+                Ok(()) => {}
+                // some systems report EEXIST instead
+                Err(nix::Error::Sys(Errno::ENOTEMPTY)) | Err(nix::Error::Sys(Errno::EEXIST))
+                    if args.ignore_non_empty =>
+                {
+                    break
+                }
+                Err(e) => {
+                    let e = Error::new(Operation::RemoveDir, dpath, e);
+                    status = status.max(e.report("rmdir"));
+                    break;
+                }
+            }
+
+            // "a/b/c" goes on to remove "a/b" and then "a", but never "" or "/"
+            next = match dpath.parent() {
+                Some(parent) if args.remove_parents && parent.file_name().is_some() => Some(parent),
+                _ => None,
+            };
         }
     }
 
@@ -0,0 +1,53 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! SELinux security contexts for newly created files. A context is stored in the
+//! `security.selinux` extended attribute, so labeling a file is just an `fsetxattr` on it. This
+//! is only built in with the `selinux` cargo feature, since most systems don't use SELinux.
+
+use crate::error;
+use crate::ExitCode;
+use std::ffi::OsStr;
+use std::os::unix::io::RawFd;
+
+/// Whether busycrate was built with support for setting contexts
+pub const ENABLED: bool = cfg!(feature = "selinux");
+
+/// Labels an open file with `context`, e.g. `system_u:object_r:tmp_t:s0`
+#[cfg(feature = "selinux")]
+pub fn set_context(fd: RawFd, context: &OsStr) -> nix::Result<()> {
+    use std::os::unix::ffi::OsStrExt;
+
+    // libselinux stores contexts with their NUL terminator, so do the same for anything that
+    // reads them back with it
+    let mut value = context.as_bytes().to_vec();
+    value.push(0);
+
+    let name = b"security.selinux\0";
+    let result = unsafe {
+        libc::fsetxattr(
+            fd,
+            name.as_ptr() as *const libc::c_char,
+            value.as_ptr() as *const libc::c_void,
+            value.len(),
+            0,
+        )
+    };
+    nix::errno::Errno::result(result).map(drop)
+}
+
+/// Without the `selinux` feature, contexts can't be set. Callers are expected to have rejected
+/// `--context` before getting here.
+#[cfg(not(feature = "selinux"))]
+pub fn set_context(_fd: RawFd, _context: &OsStr) -> nix::Result<()> {
+    Err(nix::Error::Sys(nix::errno::Errno::EOPNOTSUPP))
+}
+
+/// Reports that `--context` was given to `applet` in a busycrate built without the `selinux`
+/// feature
+pub fn unsupported(applet: &str) -> ExitCode {
+    let message = "--context isn't supported: busycrate was built without the selinux feature";
+    error::report(applet, None, message);
+    ExitCode::InvalidUsage
+}
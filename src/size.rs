@@ -0,0 +1,213 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! Formatting of file and disk sizes, shared by every command that reports them. The units follow
+//! the `--block-size` conventions used by the GNU utilities, so `K` is 1024 bytes, `KB` is 1000
+//! bytes, and so on.
+
+/// How a number of bytes gets rendered for display
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SizeFormat {
+    /// Divided by a fixed unit, rounding up. The suffix is printed right after the number, and is
+    /// only non-empty when the unit was given without a number, e.g. `--block-size=M`.
+    Units { unit: u64, suffix: &'static str },
+    /// Scaled down by the largest power of `base` (1024 or 1000) that keeps the number readable,
+    /// and suffixed with that power's letter, e.g. `4.0K` or `12M`
+    Human { base: u64 },
+}
+
+/// Every suffix a block size can be given with, and how many bytes it stands for
+const SUFFIXES: [(&str, u64); 18] = [
+    ("K", 1 << 10),
+    ("M", 1 << 20),
+    ("G", 1 << 30),
+    ("T", 1 << 40),
+    ("P", 1 << 50),
+    ("E", 1 << 60),
+    ("KiB", 1 << 10),
+    ("MiB", 1 << 20),
+    ("GiB", 1 << 30),
+    ("TiB", 1 << 40),
+    ("PiB", 1 << 50),
+    ("EiB", 1 << 60),
+    ("KB", 1_000),
+    ("MB", 1_000_000),
+    ("GB", 1_000_000_000),
+    ("TB", 1_000_000_000_000),
+    ("PB", 1_000_000_000_000_000),
+    ("EB", 1_000_000_000_000_000_000),
+];
+
+impl SizeFormat {
+    /// Plain byte counts, which is how `ls -l` shows file sizes by default
+    pub const BYTES: SizeFormat = SizeFormat::Units { unit: 1, suffix: "" };
+
+    /// Parses a block size in the form accepted by `--block-size` and the `BLOCK_SIZE`
+    /// environment variable: `human-readable`, `si`, or an optional number followed by an
+    /// optional unit suffix like `K`, `MiB` or `GB`. Returns `None` if the size isn't valid.
+    pub fn parse(spec: &str) -> Option<SizeFormat> {
+        match spec {
+            "human-readable" => return Some(SizeFormat::Human { base: 1024 }),
+            "si" => return Some(SizeFormat::Human { base: 1000 }),
+            _ => {}
+        }
+
+        let digits_end = spec
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(spec.len());
+        let (digits, suffix) = spec.split_at(digits_end);
+
+        let number: u64 = if digits.is_empty() { 1 } else { digits.parse().ok()? };
+        let (suffix, multiplier) = if suffix.is_empty() {
+            ("", 1)
+        } else {
+            // lowercase k is accepted for kilo since that's the proper SI prefix
+            let suffix = match suffix.strip_prefix('k') {
+                Some(rest) => format!("K{}", rest),
+                None => suffix.to_string(),
+            };
+            *SUFFIXES.iter().find(|(s, _)| *s == suffix)?
+        };
+
+        let unit = number.checked_mul(multiplier)?;
+        if unit == 0 {
+            return None;
+        }
+
+        // the suffix is only shown when it was the entire unit. --block-size=1K means "count in
+        // kibibytes", whereas --block-size=K means "count in kibibytes and say so"
+        let suffix = if digits.is_empty() { suffix } else { "" };
+        Some(SizeFormat::Units { unit, suffix })
+    }
+
+    /// The block size configured by the environment, checking each variable in order. Invalid
+    /// values are ignored, just like unset ones.
+    pub fn from_env(vars: &[&str]) -> Option<SizeFormat> {
+        vars.iter()
+            .filter_map(|var| std::env::var(var).ok())
+            .find_map(|spec| SizeFormat::parse(&spec))
+    }
+
+    pub fn format(&self, bytes: u64) -> String {
+        match *self {
+            SizeFormat::Units { unit, suffix } => {
+                format!("{}{}", bytes.div_ceil(unit), suffix)
+            }
+            SizeFormat::Human { base } => format_human(bytes, base),
+        }
+    }
+}
+
+fn format_human(bytes: u64, base: u64) -> String {
+    // SI uses a lowercase k for kilo, but every other prefix is the same
+    let prefixes: &[u8] = if base == 1000 { b"kMGTPE" } else { b"KMGTPE" };
+
+    if bytes < base {
+        return bytes.to_string();
+    }
+
+    let mut value = bytes as f64;
+    let mut power = 0;
+    while value >= base as f64 && power < prefixes.len() {
+        value /= base as f64;
+        power += 1;
+    }
+
+    // sizes are always rounded up so that a nearly full block never looks empty. Small values get
+    // one decimal place, but if rounding would push them past 10 they're shown as integers anyways
+    let tenths = (value * 10.0).ceil();
+    if tenths < 100.0 {
+        return format!("{:.1}{}", tenths / 10.0, prefixes[power - 1] as char);
+    }
+
+    let whole = value.ceil();
+    if whole >= base as f64 && power < prefixes.len() {
+        // e.g. 1023.5K rounds to 1024K, which is better shown as 1.0M
+        return format!("1.0{}", prefixes[power] as char);
+    }
+    format!("{}{}", whole, prefixes[power - 1] as char)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn units(unit: u64, suffix: &'static str) -> Option<SizeFormat> {
+        Some(SizeFormat::Units { unit, suffix })
+    }
+
+    #[test]
+    fn parse_suffixes() {
+        assert_eq!(SizeFormat::parse("K"), units(1024, "K"));
+        assert_eq!(SizeFormat::parse("k"), units(1024, "K"));
+        assert_eq!(SizeFormat::parse("KiB"), units(1024, "KiB"));
+        assert_eq!(SizeFormat::parse("KB"), units(1000, "KB"));
+        assert_eq!(SizeFormat::parse("kB"), units(1000, "KB"));
+        assert_eq!(SizeFormat::parse("MiB"), units(1 << 20, "MiB"));
+        assert_eq!(SizeFormat::parse("EB"), units(1_000_000_000_000_000_000, "EB"));
+        assert_eq!(SizeFormat::parse("human-readable"), Some(SizeFormat::Human { base: 1024 }));
+        assert_eq!(SizeFormat::parse("si"), Some(SizeFormat::Human { base: 1000 }));
+    }
+
+    #[test]
+    fn parse_numbers() {
+        // a number hides the suffix, since it's no longer the whole unit
+        assert_eq!(SizeFormat::parse("1K"), units(1024, ""));
+        assert_eq!(SizeFormat::parse("2M"), units(2 << 20, ""));
+        assert_eq!(SizeFormat::parse("4096"), units(4096, ""));
+        assert_eq!(SizeFormat::parse("1"), Some(SizeFormat::BYTES));
+    }
+
+    #[test]
+    fn parse_invalid() {
+        for spec in ["0", "0K", "Q", "1Q", "Kb", "-1", "1.5K", "K1"].iter() {
+            assert_eq!(SizeFormat::parse(spec), None, "{}", spec);
+        }
+        // too big to fit in a u64, either as written or once multiplied out
+        assert_eq!(SizeFormat::parse("99999999999999999999"), None);
+        assert_eq!(SizeFormat::parse("18446744073709551615K"), None);
+        assert_eq!(SizeFormat::parse("16E"), None);
+    }
+
+    #[test]
+    fn format_units() {
+        assert_eq!(SizeFormat::BYTES.format(0), "0");
+        assert_eq!(SizeFormat::BYTES.format(12345), "12345");
+        // partial units round up
+        let kibibytes = SizeFormat::Units { unit: 1024, suffix: "K" };
+        assert_eq!(kibibytes.format(0), "0K");
+        assert_eq!(kibibytes.format(1), "1K");
+        assert_eq!(kibibytes.format(1024), "1K");
+        assert_eq!(kibibytes.format(1025), "2K");
+    }
+
+    #[test]
+    fn format_human_binary() {
+        let human = SizeFormat::Human { base: 1024 };
+        assert_eq!(human.format(0), "0");
+        assert_eq!(human.format(1023), "1023");
+        assert_eq!(human.format(1024), "1.0K");
+        assert_eq!(human.format(1025), "1.1K");
+        assert_eq!(human.format(1536), "1.5K");
+        // 9.99K would round to 10.0K, so it's shown without the decimal
+        assert_eq!(human.format(10 * 1024 - 1), "10K");
+        assert_eq!(human.format(10 * 1024 + 1), "11K");
+        assert_eq!(human.format(1023 * 1024), "1023K");
+        // 1023.5K rounds up to 1024K, which is really 1.0M
+        assert_eq!(human.format(1023 * 1024 + 512), "1.0M");
+        assert_eq!(human.format(1 << 20), "1.0M");
+        assert_eq!(human.format(u64::MAX), "16E");
+    }
+
+    #[test]
+    fn format_human_si() {
+        let si = SizeFormat::Human { base: 1000 };
+        assert_eq!(si.format(999), "999");
+        assert_eq!(si.format(1000), "1.0k");
+        assert_eq!(si.format(1001), "1.1k");
+        assert_eq!(si.format(999_999), "1.0M");
+        assert_eq!(si.format(1_500_000), "1.5M");
+        assert_eq!(si.format(20_000_000_000), "20G");
+    }
+}
@@ -7,13 +7,18 @@
 //! with the latter usecase.
 
 use nix::fcntl::OFlag;
-use nix::sys::stat::Mode;
+use nix::sys::stat::{utimensat, Mode, UtimensatFlags};
 use nix::errno::Errno;
 use nix::time;
 use nix::sys::time::TimeSpec;
+use std::ffi::OsStr;
 use std::path::Path;
 use libc::timespec;
+use crate::datetime;
+use crate::applet::{self, Applet};
+use crate::error::{self, Error, Operation};
 use crate::{ExitCode, FdPathDropper};
+use clap::{App, Arg, ArgMatches, SubCommand};
 
 pub struct Args<'a> {
     /// Files to touch
@@ -24,6 +29,120 @@ pub struct Args<'a> {
     pub atime: bool,
     /// Update mtime of the file if it exists
     pub mtime: bool,
+    /// A human readable date to use instead of the current time
+    pub date: Option<&'a str>,
+    /// A POSIX `[[CC]YY]MMDDhhmm[.SS]` timestamp to use instead of the current time
+    pub stamp: Option<&'a str>,
+    /// A file to copy the atime and mtime from instead of using the current time. `date` is
+    /// relative to these times when both are given.
+    pub reference: Option<&'a Path>,
+    /// Update the times of symlinks themselves rather than the files they point to
+    pub no_dereference: bool,
+    /// SELinux security context to label created files with
+    pub context: Option<&'a OsStr>,
+}
+
+pub struct Touch;
+
+impl Applet for Touch {
+    fn name(&self) -> &'static str {
+        "touch"
+    }
+
+    fn command(&self) -> App<'static, 'static> {
+        SubCommand::with_name("touch")
+            .about("Create files and update their modified or access times")
+            .args(&[
+                Arg::with_name("files")
+                    .takes_value(true)
+                    .multiple(true)
+                    .required(true),
+                Arg::with_name("no-create")
+                    .short("c")
+                    .long("no-create")
+                    .help("Do not create any files"),
+                Arg::with_name("atime-only")
+                    .short("a")
+                    .long("atime")
+                    .help("Only update atime"),
+                Arg::with_name("mtime-only")
+                    .short("m")
+                    .long("mtime")
+                    .help("Only update mtime"),
+                Arg::with_name("date")
+                    .short("d")
+                    .long("date")
+                    .takes_value(true)
+                    .value_name("STRING")
+                    // relative dates like "-2 hours" start with a hyphen
+                    .allow_hyphen_values(true)
+                    .help("Use a date like '2024-01-01 12:00 +0200', '@1700000000' or '2 hours ago' instead of the current time"),
+                Arg::with_name("stamp")
+                    .short("t")
+                    .takes_value(true)
+                    .value_name("STAMP")
+                    .conflicts_with("date")
+                    .help("Use [[CC]YY]MMDDhhmm[.SS] in local time instead of the current time"),
+                Arg::with_name("reference")
+                    .short("r")
+                    .long("reference")
+                    .takes_value(true)
+                    .value_name("FILE")
+                    .conflicts_with("stamp")
+                    .help("Use the times of FILE instead of the current time"),
+                Arg::with_name("no-dereference")
+                    .short("h")
+                    .long("no-dereference")
+                    .help("Update the times of symlinks instead of the files they point to"),
+                Arg::with_name("context")
+                    .short("Z")
+                    .long("context")
+                    .takes_value(true)
+                    .value_name("CTX")
+                    .help("Set the SELinux security context of created files to CTX"),
+            ])
+    }
+
+    fn run(&self, touch_args: &ArgMatches) -> ExitCode {
+        if touch_args.is_present("context") && !crate::selinux::ENABLED {
+            return crate::selinux::unsupported("touch");
+        }
+
+        let paths = touch_args
+            .values_of_os("files")
+            .map(applet::map_os_args_to_path_vec)
+            .unwrap_or_default();
+
+        let create = !touch_args.is_present("no-create");
+        let mtime_only = touch_args.is_present("mtime-only");
+        let atime_only = touch_args.is_present("atime-only");
+        let atime = atime_only || !mtime_only;
+        let mtime = mtime_only || !atime_only;
+
+        // dates are plain ASCII, so anything that isn't UTF-8 can't be one
+        let date = touch_args.value_of_os("date");
+        let stamp = touch_args.value_of_os("stamp");
+        for spec in date.iter().chain(stamp.iter()) {
+            if spec.to_str().is_none() {
+                let spec = spec.to_string_lossy();
+                error::report("touch", None, format_args!("invalid date format '{}'", spec));
+                return ExitCode::InvalidUsage;
+            }
+        }
+
+        let touch_args = Args {
+            paths,
+            create,
+            atime,
+            mtime,
+            date: date.and_then(OsStr::to_str),
+            stamp: stamp.and_then(OsStr::to_str),
+            reference: touch_args.value_of_os("reference").map(Path::new),
+            no_dereference: touch_args.is_present("no-dereference"),
+            context: touch_args.value_of_os("context"),
+        };
+        return main(touch_args);
+    }
 }
 
 pub fn main(args: Args) -> ExitCode {
@@ -35,88 +154,137 @@ pub fn main(args: Args) -> ExitCode {
 
 fn main_code(args: Args) -> Result<(), ExitCode> {
     if args.paths.is_empty() {
-        eprintln!("missing file operand");
+        error::report("touch", None, "missing file operand");
         eprintln!("Try 'touch --help' for more information");
         return Err(ExitCode::InvalidUsage)
     }
 
-    for fpath in args.paths {
-        let mut flags = OFlag::O_WRONLY;
-        if args.create {
-            // allow the user to disable file creation. this will have the effect of changing the
-            // atime/mtime in the case that the file exists, but is a nop if the file doesn't
-            flags |= OFlag::O_CREAT;
+    let clock = time::ClockId::CLOCK_REALTIME;
+    let time_now = match time::clock_gettime(clock) {
+        Ok(ts) => ts,
+        Err(e) => return Err(Error::without_path(Operation::GetTime, e).report("touch")),
+    };
+
+    // -h copies the times of a symlink itself, just like it sets them
+    let reference_stat = if args.no_dereference {
+        nix::sys::stat::lstat
+    } else {
+        nix::sys::stat::stat
+    };
+    let (base_atime, base_mtime): (TimeSpec, TimeSpec) = match args.reference {
+        Some(rpath) => match reference_stat(rpath) {
+            Ok(s) => {
+                let st_atime = timespec {
+                    tv_sec: s.st_atime,
+                    tv_nsec: s.st_atime_nsec,
+                };
+                let st_mtime = timespec {
+                    tv_sec: s.st_mtime,
+                    tv_nsec: s.st_mtime_nsec,
+                };
+                (st_atime.into(), st_mtime.into())
+            }
+            Err(e) => return Err(Error::new(Operation::Stat, rpath, e).report("touch")),
+        },
+        None => (time_now, time_now),
+    };
+
+    let new_times = if let Some(spec) = args.date {
+        // a relative date moves each of the reference times separately
+        let atime = datetime::parse_date(spec, base_atime);
+        let mtime = datetime::parse_date(spec, base_mtime);
+        match atime.zip(mtime) {
+            Some(times) => times,
+            None => {
+                error::report("touch", None, format_args!("invalid date format '{}'", spec));
+                return Err(ExitCode::InvalidUsage);
+            }
         }
+    } else if let Some(spec) = args.stamp {
+        match datetime::parse_posix_stamp(spec, time_now) {
+            Some(ts) => (ts, ts),
+            None => {
+                error::report("touch", None, format_args!("invalid date format '{}'", spec));
+                return Err(ExitCode::InvalidUsage);
+            }
+        }
+    } else if args.reference.is_some() {
+        (base_atime, base_mtime)
+    } else {
+        // the kernel fills in the current time itself, which also lets us touch files that we
+        // can write to but don't own
+        (special_time(libc::UTIME_NOW), special_time(libc::UTIME_NOW))
+    };
 
-        let mode = Mode::S_IRUSR | Mode::S_IWUSR; // user can read+write
-        let mode = mode | Mode::S_IRGRP | Mode::S_IWGRP; // group can read+write
-        let mode = mode | Mode::S_IROTH | Mode::S_IWOTH; // others can read+write
+    // UTIME_OMIT leaves a time as it is without having to stat the file first
+    let new_atime = if args.atime { new_times.0 } else { special_time(libc::UTIME_OMIT) };
+    let new_mtime = if args.mtime { new_times.1 } else { special_time(libc::UTIME_OMIT) };
+    let flag = if args.no_dereference {
+        UtimensatFlags::NoFollowSymlink
+    } else {
+        UtimensatFlags::FollowSymlink
+    };
 
-        let fd = match nix::fcntl::open(fpath, flags, mode) {
-            Ok(fd) => fd,
+    // keep going after errors so that one bad path doesn't stop the rest from being touched, and
+    // report the worst thing that went wrong
+    let mut status = ExitCode::Success;
+    for fpath in args.paths {
+        let result = match utimensat(None, fpath, &new_atime, &new_mtime, flag) {
+            Ok(()) => Ok(()),
             // If the file doesn't exist, and we've been told not to create files, then this
             // condition isn't an error. That's just the expected behavior of that option.
             Err(nix::Error::Sys(Errno::ENOENT)) if !args.create => continue,
-            Err(e) => {
-                eprintln!("Unable to create file '{}': {}", fpath.display(), e);
-                return Err(ExitCode::UnknownErr)
+            // -h never creates files, since there'd be no symlink to update
+            Err(nix::Error::Sys(Errno::ENOENT)) if !args.no_dereference => {
+                create_file(fpath, &new_atime, &new_mtime, args.context)
             }
+            Err(e) => Err(Error::new(Operation::SetTimes, fpath, e).report("touch")),
         };
-        let _dropper = FdPathDropper::new(fd, fpath.display());
-        update_fd_times(fd, args.atime, args.mtime, fpath)?;
+        if let Err(e) = result {
+            status = status.max(e);
+        }
+    }
+
+    match status {
+        ExitCode::Success => Ok(()),
+        e => Err(e),
     }
-    return Ok(())
 }
 
-/// If we've been configured to update file times, this function will set them to the system time.
-fn update_fd_times(fd: i32, update_atime: bool, update_mtime: bool, fpath: &Path) -> Result<(), ExitCode> {
-    if update_atime || update_mtime {
-        // only bother with these extra syscalls if we're actually expected to update the times
-        // on this file
-
-        let clock = time::ClockId::CLOCK_REALTIME;
-        let time_now = match time::clock_gettime(clock) {
-            Ok(ts) => ts,
-            Err(e) => {
-                eprintln!("Unable to get system time: {}", e);
-                return Err(ExitCode::Time);
-            }
-        };
+/// Creates an empty file and sets its times. This is the only time touch opens anything, since
+/// opening existing files for writing fails on read-only files and directories, and blocks on
+/// FIFOs.
+fn create_file(
+    fpath: &Path,
+    atime: &TimeSpec,
+    mtime: &TimeSpec,
+    context: Option<&OsStr>,
+) -> Result<(), ExitCode> {
+    let flags = OFlag::O_WRONLY | OFlag::O_CREAT | OFlag::O_NOCTTY | OFlag::O_NONBLOCK;
 
-        let (new_atime, new_mtime): (TimeSpec, TimeSpec);
-        if !update_atime || !update_mtime {
-            // in this case, we need to get the old times already set on the file
-            let s = match nix::sys::stat::fstat(fd) {
-                Ok(s) => s,
-                Err(e) => {
-                    eprintln!("Unable to stat file '{}': {}", fpath.display(), e);
-                    return Err(ExitCode::Stat);
-                },
-            };
-            let st_atime = timespec {
-                tv_sec: s.st_atime,
-                tv_nsec: s.st_atime_nsec,
-            }.into();
-            let st_mtime = timespec {
-                tv_sec: s.st_mtime,
-                tv_nsec: s.st_mtime_nsec,
-            }.into();
-
-            new_atime = if !update_atime { st_atime } else { time_now };
-            new_mtime = if !update_mtime { st_mtime } else { time_now };
-        } else {
-            new_atime = time_now;
-            new_mtime = time_now;
-        }
+    let mode = Mode::S_IRUSR | Mode::S_IWUSR; // user can read+write
+    let mode = mode | Mode::S_IRGRP | Mode::S_IWGRP; // group can read+write
+    let mode = mode | Mode::S_IROTH | Mode::S_IWOTH; // others can read+write
 
-        if let Err(e) = nix::sys::stat::futimens(fd, &new_atime, &new_mtime) {
-            eprintln!("Couldn't modify times on '{}': {}", fpath.display(), e);
+    let fd = match nix::fcntl::open(fpath, flags, mode) {
+        Ok(fd) => fd,
+        Err(e) => return Err(Error::new(Operation::CreateFile, fpath, e).report("touch")),
+    };
+    let _dropper = FdPathDropper::new(fd, "touch", fpath);
 
-            // Stat is a better description of the error here than Time since we're modifying a file's
-            // metadata, not reading/setting clocks
-            return Err(ExitCode::Stat);
+    if let Some(context) = context {
+        if let Err(e) = crate::selinux::set_context(fd, context) {
+            return Err(Error::new(Operation::SetContext, fpath, e).report("touch"));
         }
     }
 
+    if let Err(e) = nix::sys::stat::futimens(fd, atime, mtime) {
+        return Err(Error::new(Operation::SetTimes, fpath, e).report("touch"));
+    }
     return Ok(());
 }
+
+/// A time with one of the special `UTIME_*` values in place of its nanoseconds
+fn special_time(nsec: libc::c_long) -> TimeSpec {
+    timespec { tv_sec: 0, tv_nsec: nsec }.into()
+}
@@ -0,0 +1,33 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! Helpers shared by the integration tests. Not every test file uses all of them.
+
+#![allow(dead_code)]
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+/// A fresh directory for a single test to work in
+pub fn test_dir(name: &str) -> PathBuf {
+    test_dir_in(&std::env::temp_dir(), name)
+}
+
+/// A fresh directory for a single test to work in, under `base`
+pub fn test_dir_in(base: &Path, name: &str) -> PathBuf {
+    let dir = base.join(format!("busycrate-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Runs busycrate with `args` from inside `dir`
+pub fn busycrate(dir: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_busycrate"))
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .unwrap()
+}
@@ -0,0 +1,79 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! mkdir and touch label what they create with `-Z`, but only when built with the `selinux`
+//! feature. tmpfs stores `security.*` xattrs itself when SELinux isn't loaded, so the labels can
+//! be checked without an SELinux system.
+
+#![cfg(all(feature = "mkdir", feature = "touch"))]
+
+mod common;
+
+use common::{busycrate, test_dir_in};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A fresh directory for a single test to work in, on tmpfs when possible
+fn test_dir(name: &str) -> PathBuf {
+    let shm = Path::new("/dev/shm");
+    let base = if shm.is_dir() { shm.to_path_buf() } else { std::env::temp_dir() };
+    test_dir_in(&base, &format!("context-{}", name))
+}
+
+#[cfg(feature = "selinux")]
+fn selinux_label(path: &Path) -> Option<Vec<u8>> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let path = CString::new(path.as_os_str().as_bytes()).unwrap();
+    let name = b"security.selinux\0";
+    let mut value = vec![0u8; 256];
+    let len = unsafe {
+        libc::getxattr(
+            path.as_ptr(),
+            name.as_ptr() as *const libc::c_char,
+            value.as_mut_ptr() as *mut libc::c_void,
+            value.len(),
+        )
+    };
+    if len < 0 {
+        return None;
+    }
+    value.truncate(len as usize);
+    Some(value)
+}
+
+#[cfg(feature = "selinux")]
+#[test]
+fn labels_created_files() {
+    let dir = test_dir("labels");
+    let context = "system_u:object_r:tmp_t:s0";
+
+    let output = busycrate(&dir, &["mkdir", "-p", "-Z", context, "a/b"]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let output = busycrate(&dir, &["touch", "--context", context, "a/b/f"]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+
+    let expected = format!("{}\0", context).into_bytes();
+    for path in ["a", "a/b", "a/b/f"].iter() {
+        assert_eq!(selinux_label(&dir.join(path)), Some(expected.clone()), "{}", path);
+    }
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[cfg(not(feature = "selinux"))]
+#[test]
+fn unsupported_without_feature() {
+    let dir = test_dir("unsupported");
+
+    for args in [&["mkdir", "-Z", "ctx", "d"][..], &["touch", "-Z", "ctx", "f"][..]].iter() {
+        let output = busycrate(&dir, args);
+        assert_eq!(output.status.code(), Some(1));
+        assert!(String::from_utf8_lossy(&output.stderr).contains("selinux feature"));
+    }
+    // nothing should be created when the label can't be applied
+    assert!(!dir.join("d").exists());
+    assert!(!dir.join("f").exists());
+    fs::remove_dir_all(dir).unwrap();
+}
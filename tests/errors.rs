@@ -0,0 +1,85 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! Every applet reports failures on files as `applet: 'path': cannot <operation>: <reason>`, and
+//! picks its exit code from the reason in the same way.
+
+#![cfg(all(feature = "ls", feature = "mkdir", feature = "rmdir", feature = "touch"))]
+
+mod common;
+
+use common::{busycrate, test_dir};
+use std::fs;
+
+#[test]
+fn missing_paths() {
+    let dir = test_dir("errors-missing");
+    let cases: &[(&[&str], &str)] = &[
+        (&["ls", "missing"], "ls: 'missing': cannot stat: "),
+        (&["mkdir", "missing/d"], "mkdir: 'missing/d': cannot create directory: "),
+        (&["rmdir", "missing"], "rmdir: 'missing': cannot remove directory: "),
+        (&["touch", "-r", "missing", "f"], "touch: 'missing': cannot stat: "),
+    ];
+
+    for (args, prefix) in cases.iter() {
+        let output = busycrate(&dir, args);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert_eq!(output.status.code(), Some(7), "{:?}", args);
+        assert!(stderr.starts_with(prefix), "{:?}: {}", args, stderr);
+    }
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn paths_under_files() {
+    let dir = test_dir("errors-not-dir");
+    fs::write(dir.join("file"), b"").unwrap();
+
+    for args in [&["ls", "file/x"][..], &["mkdir", "file/x"], &["rmdir", "file/x"]].iter() {
+        let output = busycrate(&dir, args);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert_eq!(output.status.code(), Some(8), "{:?}", args);
+        assert!(stderr.starts_with(&format!("{}: 'file/x': cannot ", args[0])), "{}", stderr);
+    }
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn existing_directories() {
+    let dir = test_dir("errors-exists");
+    fs::create_dir(dir.join("d")).unwrap();
+    fs::write(dir.join("file"), b"").unwrap();
+
+    let output = busycrate(&dir, &["mkdir", "d"]);
+    assert_eq!(output.status.code(), Some(6));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.starts_with("mkdir: 'd': cannot create directory: "), "{}", stderr);
+
+    // something already existing is the least of mkdir's problems
+    let output = busycrate(&dir, &["mkdir", "d", "missing/d", "e"]);
+    assert_eq!(output.status.code(), Some(7));
+    assert!(dir.join("e").is_dir());
+
+    // with -p, only the operand itself can already exist
+    assert_eq!(busycrate(&dir, &["mkdir", "-p", "file"]).status.code(), Some(6));
+    assert_eq!(busycrate(&dir, &["mkdir", "-p", "file/d"]).status.code(), Some(8));
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn usage_errors() {
+    let dir = test_dir("errors-usage");
+    let cases: &[(&[&str], &str)] = &[
+        (&["ls", "--block-size=x"], "ls: invalid --block-size argument 'x'\n"),
+        (&["mkdir", "-m", "u+q", "d"], "mkdir: invalid mode 'u+q'\n"),
+        (&["touch", "-d", "someday", "f"], "touch: invalid date format 'someday'\n"),
+    ];
+
+    for (args, expected) in cases.iter() {
+        let output = busycrate(&dir, args);
+        assert_eq!(output.status.code(), Some(1), "{:?}", args);
+        assert_eq!(String::from_utf8_lossy(&output.stderr), *expected);
+    }
+    fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,66 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! touch should work through every operand even when some of them fail, and exit with the most
+//! severe error it ran into.
+
+#![cfg(feature = "touch")]
+
+mod common;
+
+use common::{busycrate, test_dir};
+use std::fs;
+use std::path::Path;
+
+fn touch(dir: &Path, args: &[&str]) -> i32 {
+    let args: Vec<_> = std::iter::once(&"touch").chain(args).copied().collect();
+    busycrate(dir, &args).status.code().unwrap()
+}
+
+#[test]
+fn all_succeed() {
+    let dir = test_dir("touch-all-succeed");
+    assert_eq!(touch(&dir, &["a", "b", "c"]), 0);
+    for name in ["a", "b", "c"].iter() {
+        assert!(dir.join(name).is_file());
+    }
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn continues_after_failure() {
+    let dir = test_dir("touch-continues");
+    // creating inside a missing directory fails, but shouldn't stop c from being created
+    assert_eq!(touch(&dir, &["a", "missing/b", "c"]), 7);
+    assert!(dir.join("a").is_file());
+    assert!(!dir.join("missing").exists());
+    assert!(dir.join("c").is_file());
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn reports_most_severe_error() {
+    let dir = test_dir("touch-severity");
+    fs::write(dir.join("file"), b"").unwrap();
+
+    // a path under a regular file is reported as not being a directory
+    assert_eq!(touch(&dir, &["file/x", "a"]), 8);
+    assert!(dir.join("a").is_file());
+
+    // which outranks a missing directory, regardless of the order they happen in
+    assert_eq!(touch(&dir, &["missing/x", "file/x", "b"]), 8);
+    assert_eq!(touch(&dir, &["file/x", "missing/x", "c"]), 8);
+    assert!(dir.join("b").is_file());
+    assert!(dir.join("c").is_file());
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn no_create_skips_missing_files() {
+    let dir = test_dir("touch-no-create");
+    fs::write(dir.join("exists"), b"").unwrap();
+    assert_eq!(touch(&dir, &["-c", "missing", "exists"]), 0);
+    assert!(!dir.join("missing").exists());
+    fs::remove_dir_all(dir).unwrap();
+}